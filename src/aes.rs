@@ -1,206 +1,383 @@
 use crate::Error;
-use std::arch::x86_64::{__m128i, _mm_loadu_si128, _mm_xor_si128, _mm_aesdec_si128, _mm_aesdeclast_si128, _mm_storeu_si128, _mm_aeskeygenassist_si128, _mm_shuffle_epi32, _mm_slli_si128, _mm_aesimc_si128, _mm_setzero_si128, _mm_aesenc_si128, _mm_aesenclast_si128, _mm_set_epi8};
-
-/// copied here from sse.rs so that we can run this in stable rust
-#[inline]
-#[allow(non_snake_case)]
-const fn _MM_SHUFFLE(z: u32, y: u32, x: u32, w: u32) -> i32 {
-    ((z << 6) | (y << 4) | (x << 2) | w) as i32
+use crate::aes_soft;
+#[cfg(target_arch = "x86_64")]
+use crate::aes_ni;
+use crate::padding::Padding;
+use crate::modes::{BlockCipher, CounterLayout, ecb_encrypt, ecb_decrypt, cbc_encrypt, cbc_decrypt, ctr_encrypt, ctr_decrypt, CtrKeyStream};
+use std::convert::TryInto;
+
+/// Expanded key for whichever backend this cpu supports, AES-NI is picked at
+/// runtime and the table driven implementation is used everywhere else.
+enum KeySchedule {
+    #[cfg(target_arch = "x86_64")]
+    AesNi(aes_ni::KeySchedule),
+    Soft(aes_soft::KeySchedule),
 }
 
-unsafe fn aes_128_key_expansion(key: __m128i, keygened: __m128i) -> __m128i {
-    let keygened = _mm_shuffle_epi32(keygened, _MM_SHUFFLE(3,3,3,3));
-    let key = _mm_xor_si128(key, _mm_slli_si128(key, 4));
-    let key = _mm_xor_si128(key, _mm_slli_si128(key, 4));
-    let key = _mm_xor_si128(key, _mm_slli_si128(key, 4));
-
-    _mm_xor_si128(key, keygened)
+#[cfg(target_arch = "x86_64")]
+fn has_aes_ni() -> bool {
+    is_x86_feature_detected!("aes")
 }
 
-unsafe fn aes128_load_key(enc_key: &[u8; 16], key_schedule: &mut [__m128i; 20]) {
-    key_schedule[0] = _mm_loadu_si128(enc_key.as_ptr() as *const __m128i);
-    key_schedule[1]  = aes_128_key_expansion(key_schedule[0], _mm_aeskeygenassist_si128(key_schedule[0], 0x01));
-    key_schedule[2]  = aes_128_key_expansion(key_schedule[1], _mm_aeskeygenassist_si128(key_schedule[1], 0x02));
-    key_schedule[3]  = aes_128_key_expansion(key_schedule[2], _mm_aeskeygenassist_si128(key_schedule[2], 0x04));
-    key_schedule[4]  = aes_128_key_expansion(key_schedule[3], _mm_aeskeygenassist_si128(key_schedule[3], 0x08));
-    key_schedule[5]  = aes_128_key_expansion(key_schedule[4], _mm_aeskeygenassist_si128(key_schedule[4], 0x10));
-    key_schedule[6]  = aes_128_key_expansion(key_schedule[5], _mm_aeskeygenassist_si128(key_schedule[5], 0x20));
-    key_schedule[7]  = aes_128_key_expansion(key_schedule[6], _mm_aeskeygenassist_si128(key_schedule[6], 0x40));
-    key_schedule[8]  = aes_128_key_expansion(key_schedule[7], _mm_aeskeygenassist_si128(key_schedule[7], 0x80));
-    key_schedule[9]  = aes_128_key_expansion(key_schedule[8], _mm_aeskeygenassist_si128(key_schedule[8], 0x1B));
-    key_schedule[10] = aes_128_key_expansion(key_schedule[9], _mm_aeskeygenassist_si128(key_schedule[9], 0x36));
-
-    key_schedule[11] = _mm_aesimc_si128(key_schedule[9]);
-    key_schedule[12] = _mm_aesimc_si128(key_schedule[8]);
-    key_schedule[13] = _mm_aesimc_si128(key_schedule[7]);
-    key_schedule[14] = _mm_aesimc_si128(key_schedule[6]);
-    key_schedule[15] = _mm_aesimc_si128(key_schedule[5]);
-    key_schedule[16] = _mm_aesimc_si128(key_schedule[4]);
-    key_schedule[17] = _mm_aesimc_si128(key_schedule[3]);
-    key_schedule[18] = _mm_aesimc_si128(key_schedule[2]);
-    key_schedule[19] = _mm_aesimc_si128(key_schedule[1]);
+/// AES with the key schedule expanded once, so repeated calls with the same
+/// key only pay for the rounds. The key size picks the variant, 16 bytes is
+/// AES-128, 24 is AES-192 and 32 is AES-256.
+pub struct Aes {
+    key_schedule: KeySchedule,
 }
 
-pub fn decrypt_aes_ecb(input: &[u8], key: &[u8; 16]) -> Result<Vec<u8>, Error> {
-    if input.len() % 16 != 0 {
-        return Err(Error::Generic("block size isn't 16"))
-    }
+impl Aes {
+    pub fn new(key: &[u8]) -> Result<Aes, Error> {
+        if key.len() != 16 && key.len() != 24 && key.len() != 32 {
+            return Err(Error::Generic("key must be 16, 24 or 32 bytes"));
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            if has_aes_ni() {
+                return Ok(Aes { key_schedule: KeySchedule::AesNi(unsafe { aes_ni::KeySchedule::new(key) }) });
+            }
+        }
 
-    let mut plain_text:Vec<u8> = vec![0u8; input.len()];
-    unsafe {
-        let mut key_schedule: [__m128i; 20] = [_mm_setzero_si128(); 20];
+        Ok(Aes { key_schedule: KeySchedule::Soft(aes_soft::KeySchedule::new(key)) })
+    }
 
-        aes128_load_key(key, &mut key_schedule);
+    pub fn encrypt_block(&self, block: &mut [u8; 16]) {
+        match &self.key_schedule {
+            #[cfg(target_arch = "x86_64")]
+            KeySchedule::AesNi(key_schedule) => unsafe { aes_ni::encrypt_block(key_schedule, block) },
+            KeySchedule::Soft(key_schedule) => aes_soft::encrypt_block(key_schedule, block),
+        }
+    }
 
-        for i in 0..(input.len() / 16) {
-            let mut m = _mm_loadu_si128((input.as_ptr() as *const __m128i).add(i));
+    pub fn decrypt_block(&self, block: &mut [u8; 16]) {
+        match &self.key_schedule {
+            #[cfg(target_arch = "x86_64")]
+            KeySchedule::AesNi(key_schedule) => unsafe { aes_ni::decrypt_block(key_schedule, block) },
+            KeySchedule::Soft(key_schedule) => aes_soft::decrypt_block(key_schedule, block),
+        }
+    }
 
-            m = _mm_xor_si128(m, key_schedule[10]);
-            m = _mm_aesdec_si128(m, key_schedule[11]);
-            m = _mm_aesdec_si128(m, key_schedule[12]);
-            m = _mm_aesdec_si128(m, key_schedule[13]);
-            m = _mm_aesdec_si128(m, key_schedule[14]);
-            m = _mm_aesdec_si128(m, key_schedule[15]);
-            m = _mm_aesdec_si128(m, key_schedule[16]);
-            m = _mm_aesdec_si128(m, key_schedule[17]);
-            m = _mm_aesdec_si128(m, key_schedule[18]);
-            m = _mm_aesdec_si128(m, key_schedule[19]);
-            m = _mm_aesdeclast_si128(m, key_schedule[0]);
+    /// buf must be a whole number of blocks, with AES-NI several blocks are
+    /// pushed through the rounds side by side
+    pub fn encrypt_blocks(&self, buf: &mut [u8]) {
+        match &self.key_schedule {
+            #[cfg(target_arch = "x86_64")]
+            KeySchedule::AesNi(key_schedule) => unsafe { aes_ni::encrypt_blocks(key_schedule, buf) },
+            KeySchedule::Soft(key_schedule) => {
+                for block in buf.chunks_exact_mut(16) {
+                    aes_soft::encrypt_block(key_schedule, block.try_into().unwrap());
+                }
+            },
+        }
+    }
 
-            _mm_storeu_si128((plain_text.as_ptr() as *mut __m128i).add(i), m);
+    /// buf must be a whole number of blocks, with AES-NI several blocks are
+    /// pushed through the rounds side by side
+    pub fn decrypt_blocks(&self, buf: &mut [u8]) {
+        match &self.key_schedule {
+            #[cfg(target_arch = "x86_64")]
+            KeySchedule::AesNi(key_schedule) => unsafe { aes_ni::decrypt_blocks(key_schedule, buf) },
+            KeySchedule::Soft(key_schedule) => {
+                for block in buf.chunks_exact_mut(16) {
+                    aes_soft::decrypt_block(key_schedule, block.try_into().unwrap());
+                }
+            },
         }
     }
+}
 
-    remove_padding(&mut plain_text)?;
+/// AES-128 for when the key is known to be 16 bytes, so construction can't
+/// fail. Derefs to `Aes` so it works with all the mode functions.
+pub struct Aes128(Aes);
 
-    Ok(plain_text)
+impl Aes128 {
+    pub fn new(key: &[u8; 16]) -> Aes128 {
+        Aes128(Aes::new(key).unwrap())
+    }
 }
 
-pub fn decrypt_aes_cbc(input: &[u8], key: &[u8; 16], iv: &[u8; 16]) -> Result<Vec<u8>, Error> {
-    if input.len() % 16 != 0 {
-        return Err(Error::Generic("block size isn't 16"))
+impl std::ops::Deref for Aes128 {
+    type Target = Aes;
+
+    fn deref(&self) -> &Aes {
+        &self.0
     }
+}
 
-    let mut plain_text:Vec<u8> = vec![0u8; input.len()];
-    unsafe {
-        let iv: __m128i = _mm_set_epi8(iv[0] as i8, iv[1] as i8, iv[2] as i8, iv[3] as i8, iv[4] as i8, iv[5] as i8, iv[6] as i8, iv[7] as i8, iv[8] as i8, iv[9] as i8, iv[10] as i8, iv[11] as i8, iv[12] as i8, iv[13] as i8, iv[14] as i8, iv[15] as i8);
-        let mut key_schedule: [__m128i; 20] = [_mm_setzero_si128(); 20];
+impl BlockCipher for Aes {
+    fn block_size(&self) -> usize {
+        16
+    }
 
-        aes128_load_key(key, &mut key_schedule);
+    fn encrypt_block(&self, block: &mut [u8]) {
+        Aes::encrypt_block(self, block.try_into().unwrap());
+    }
 
-        for i in 0..(input.len() / 16) {
-            let mut m = _mm_loadu_si128((input.as_ptr() as *const __m128i).add(i));
+    fn decrypt_block(&self, block: &mut [u8]) {
+        Aes::decrypt_block(self, block.try_into().unwrap());
+    }
 
-            m = _mm_xor_si128(m, key_schedule[10]);
-            m = _mm_aesdec_si128(m, key_schedule[11]);
-            m = _mm_aesdec_si128(m, key_schedule[12]);
-            m = _mm_aesdec_si128(m, key_schedule[13]);
-            m = _mm_aesdec_si128(m, key_schedule[14]);
-            m = _mm_aesdec_si128(m, key_schedule[15]);
-            m = _mm_aesdec_si128(m, key_schedule[16]);
-            m = _mm_aesdec_si128(m, key_schedule[17]);
-            m = _mm_aesdec_si128(m, key_schedule[18]);
-            m = _mm_aesdec_si128(m, key_schedule[19]);
-            m = _mm_aesdeclast_si128(m, key_schedule[0]);
+    fn encrypt_blocks(&self, buf: &mut [u8]) {
+        Aes::encrypt_blocks(self, buf);
+    }
 
-            match i {
-                0 => m = _mm_xor_si128(m, iv),
-                _ => m = _mm_xor_si128(m, *(input.as_ptr() as * const __m128i).add(i - 1)),
-            }
+    fn decrypt_blocks(&self, buf: &mut [u8]) {
+        Aes::decrypt_blocks(self, buf);
+    }
+}
 
-            _mm_storeu_si128((plain_text.as_ptr() as *mut __m128i).add(i), m);
-        }
+impl BlockCipher for Aes128 {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        self.0.encrypt_block(block.try_into().unwrap());
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        self.0.decrypt_block(block.try_into().unwrap());
     }
 
-    remove_padding(&mut plain_text)?;
+    fn encrypt_blocks(&self, buf: &mut [u8]) {
+        self.0.encrypt_blocks(buf);
+    }
+
+    fn decrypt_blocks(&self, buf: &mut [u8]) {
+        self.0.decrypt_blocks(buf);
+    }
+}
+
+/// a buffer holding input with room for its padding
+fn with_padding_room(input: &[u8], block_size: usize) -> Vec<u8> {
+    let mut buf = vec![0; (input.len() / block_size + 1) * block_size];
+    buf[..input.len()].copy_from_slice(input);
+    buf
+}
+
+/// Pads the first msg_len bytes of buf and encrypts them without allocating,
+/// buf needs room for the padding. Returns the cipher text length.
+pub fn encrypt_ecb_in_place<C: BlockCipher>(cipher: &C, padding: Padding, buf: &mut [u8], msg_len: usize) -> Result<usize, Error> {
+    let len = padding.pad(buf, msg_len, cipher.block_size())?;
+
+    ecb_encrypt(cipher, &mut buf[..len])?;
+
+    Ok(len)
+}
+
+/// Decrypts buf without allocating and returns the plain text length with
+/// the padding stripped.
+pub fn decrypt_ecb_in_place<C: BlockCipher>(cipher: &C, padding: Padding, buf: &mut [u8]) -> Result<usize, Error> {
+    ecb_decrypt(cipher, buf)?;
+
+    padding.unpadded_len(buf, cipher.block_size())
+}
+
+/// Pads the first msg_len bytes of buf and encrypts them without allocating,
+/// buf needs room for the padding. Returns the cipher text length.
+pub fn encrypt_cbc_in_place<C: BlockCipher>(cipher: &C, iv: &[u8], padding: Padding, buf: &mut [u8], msg_len: usize) -> Result<usize, Error> {
+    let len = padding.pad(buf, msg_len, cipher.block_size())?;
+
+    cbc_encrypt(cipher, iv, &mut buf[..len])?;
+
+    Ok(len)
+}
+
+/// Decrypts buf without allocating and returns the plain text length with
+/// the padding stripped.
+pub fn decrypt_cbc_in_place<C: BlockCipher>(cipher: &C, iv: &[u8], padding: Padding, buf: &mut [u8]) -> Result<usize, Error> {
+    cbc_decrypt(cipher, iv, buf)?;
+
+    padding.unpadded_len(buf, cipher.block_size())
+}
+
+pub fn encrypt_ecb<C: BlockCipher>(cipher: &C, input: &[u8], padding: Padding) -> Result<Vec<u8>, Error> {
+    let mut cipher_text = with_padding_room(input, cipher.block_size());
+    let len = encrypt_ecb_in_place(cipher, padding, &mut cipher_text, input.len())?;
+    cipher_text.truncate(len);
+
+    Ok(cipher_text)
+}
+
+pub fn decrypt_ecb<C: BlockCipher>(cipher: &C, input: &[u8], padding: Padding) -> Result<Vec<u8>, Error> {
+    let mut plain_text = input.to_vec();
+    let len = decrypt_ecb_in_place(cipher, padding, &mut plain_text)?;
+    plain_text.truncate(len);
+
+    Ok(plain_text)
+}
+
+pub fn encrypt_cbc<C: BlockCipher>(cipher: &C, input: &[u8], iv: &[u8], padding: Padding) -> Result<Vec<u8>, Error> {
+    let mut cipher_text = with_padding_room(input, cipher.block_size());
+    let len = encrypt_cbc_in_place(cipher, iv, padding, &mut cipher_text, input.len())?;
+    cipher_text.truncate(len);
+
+    Ok(cipher_text)
+}
+
+pub fn decrypt_cbc<C: BlockCipher>(cipher: &C, input: &[u8], iv: &[u8], padding: Padding) -> Result<Vec<u8>, Error> {
+    let mut plain_text = input.to_vec();
+    let len = decrypt_cbc_in_place(cipher, iv, padding, &mut plain_text)?;
+    plain_text.truncate(len);
 
     Ok(plain_text)
 }
 
-pub fn encrypt_aes_cbc(input: &[u8], key: &[u8; 16], iv: &[u8; 16]) -> Result<Vec<u8>, Error> {
-    let mut input = input.to_vec();
-    add_padding(&mut input, 16)?;
+pub fn encrypt_aes_ecb(input: &[u8], key: &[u8]) -> Result<Vec<u8>, Error> {
+    encrypt_ecb(&Aes::new(key)?, input, Padding::Pkcs7)
+}
 
-    let mut cipher_text:Vec<u8> = vec![0u8; input.len()];
-    for (i, b) in iv.iter().enumerate() {
-        cipher_text[i] = *b;
+/// ECB without padding, the input must already be a multiple of the block size
+#[cfg(test)]
+pub fn encrypt_aes_ecb_no_padding(input: &[u8], key: &[u8]) -> Result<Vec<u8>, Error> {
+    if input.len() % 16 != 0 {
+        return Err(Error::Generic("block size isn't 16"))
     }
-    unsafe {
-        let mut key_schedule: [__m128i; 20] = [_mm_setzero_si128(); 20];
 
-        aes128_load_key(key, &mut key_schedule);
+    encrypt_ecb(&Aes::new(key)?, input, Padding::None)
+}
 
-        for i in 0..(input.len() / 16) {
-            let mut m = _mm_loadu_si128((input.as_ptr() as *const __m128i).add(i));
+pub fn decrypt_aes_ecb(input: &[u8], key: &[u8]) -> Result<Vec<u8>, Error> {
+    decrypt_ecb(&Aes::new(key)?, input, Padding::Pkcs7)
+}
+
+#[cfg(test)]
+pub fn decrypt_aes_ecb_no_padding(input: &[u8], key: &[u8]) -> Result<Vec<u8>, Error> {
+    decrypt_ecb(&Aes::new(key)?, input, Padding::None)
+}
 
-            m = _mm_xor_si128(m, *(cipher_text.as_ptr() as *const __m128i).add(match i { 0..=1 => 0, _ => i - 1}));
+/// CBC with a detached iv, the cipher text is only the encrypted blocks and
+/// the iv has to be passed along separately.
+pub fn decrypt_aes_cbc(input: &[u8], key: &[u8], iv: &[u8; 16]) -> Result<Vec<u8>, Error> {
+    decrypt_cbc(&Aes::new(key)?, input, iv, Padding::Pkcs7)
+}
 
-            m = _mm_xor_si128(m, key_schedule[0]);
-            m = _mm_aesenc_si128(m, key_schedule[1]);
-            m = _mm_aesenc_si128(m, key_schedule[2]);
-            m = _mm_aesenc_si128(m, key_schedule[3]);
-            m = _mm_aesenc_si128(m, key_schedule[4]);
-            m = _mm_aesenc_si128(m, key_schedule[5]);
-            m = _mm_aesenc_si128(m, key_schedule[6]);
-            m = _mm_aesenc_si128(m, key_schedule[7]);
-            m = _mm_aesenc_si128(m, key_schedule[8]);
-            m = _mm_aesenc_si128(m, key_schedule[9]);
-            m = _mm_aesenclast_si128(m, key_schedule[10]);
+/// CBC with a detached iv, the iv is not part of the returned cipher text.
+pub fn encrypt_aes_cbc(input: &[u8], key: &[u8], iv: &[u8; 16]) -> Result<Vec<u8>, Error> {
+    encrypt_cbc(&Aes::new(key)?, input, iv, Padding::Pkcs7)
+}
 
-            _mm_storeu_si128((cipher_text.as_ptr() as *mut __m128i).add(i), m);
-        }
-    }
+/// CBC with the iv sent as the first block of the cipher text.
+#[cfg(test)]
+pub fn encrypt_aes_cbc_iv_prefixed(input: &[u8], key: &[u8], iv: &[u8; 16]) -> Result<Vec<u8>, Error> {
+    let mut cipher_text = iv.to_vec();
+    cipher_text.extend(encrypt_cbc(&Aes::new(key)?, input, iv, Padding::Pkcs7)?);
 
     Ok(cipher_text)
 }
 
-pub fn add_padding(plain_text: &mut Vec<u8>, block_size: usize) -> Result<(), Error> {
-    if block_size == 0 {
-        return Err(Error::Generic("block size must be > 0"));
+/// CBC where the first block of the input is the iv.
+#[cfg(test)]
+pub fn decrypt_aes_cbc_iv_prefixed(input: &[u8], key: &[u8]) -> Result<Vec<u8>, Error> {
+    if input.len() < 16 {
+        return Err(Error::Generic("cipher text is missing the iv"));
     }
 
-    let nr_of_bytes_to_extend = match plain_text.len() % block_size {
-        0 => block_size,
-        i => block_size - i
-    };
-    plain_text.extend(&vec![nr_of_bytes_to_extend as u8; nr_of_bytes_to_extend]);
+    let (iv, cipher_text) = input.split_at(16);
+    decrypt_cbc(&Aes::new(key)?, cipher_text, iv, Padding::Pkcs7)
+}
 
-    Ok(())
+pub fn encrypt_aes_ctr(input: &[u8], key: &[u8], layout: &CounterLayout) -> Result<Vec<u8>, Error> {
+    let mut output = input.to_vec();
+    ctr_encrypt(&Aes::new(key)?, layout, &mut output)?;
+
+    Ok(output)
 }
 
-fn remove_padding(plain_text: &mut Vec<u8>) -> Result<(), Error> {
-    if plain_text.is_empty() {
-        return Err(Error::Generic("empty buffer, not padded"));
-    }
-    if plain_text.len() % 16 != 0 {
-        return Err(Error::Generic("buffer not padded to 16"));
-    }
+pub fn decrypt_aes_ctr(input: &[u8], key: &[u8], layout: &CounterLayout) -> Result<Vec<u8>, Error> {
+    let mut output = input.to_vec();
+    ctr_decrypt(&Aes::new(key)?, layout, &mut output)?;
 
-    if plain_text[plain_text.len() - 1] < 17 && plain_text[plain_text.len() - 1] != 0 {
-        let pad_byte = plain_text[plain_text.len() - 1];
+    Ok(output)
+}
 
-        if (plain_text.len() as i32) - (pad_byte as i32) < 0 {
-            return Err(Error::Generic("pad byte wrong"));
-        }
+/// Re-encrypts new_text over a CTR cipher text starting at offset, only the
+/// key stream blocks that are touched get generated. Writing past the end
+/// extends the cipher text.
+pub fn edit_aes_ctr(cipher_text: &mut Vec<u8>, key: &[u8], layout: &CounterLayout, offset: usize, new_text: &[u8]) -> Result<(), Error> {
+    if offset > cipher_text.len() {
+        return Err(Error::Generic("offset is past the end of the cipher text"));
+    }
 
-        for i in 1..(pad_byte + 1) {
-            if plain_text[plain_text.len() - i as usize] != pad_byte {
-                return Err(Error::Generic("pad byte wrong"));
-            }
-        }
+    let cipher = Aes::new(key)?;
+    let mut key_stream = CtrKeyStream::new(&cipher, *layout)?;
 
-        plain_text.resize(plain_text.len() - pad_byte as usize, 0u8);
-    } else {
-        return Err(Error::Generic("pad byte wrong"));
+    let end = offset + new_text.len();
+    if end > cipher_text.len() {
+        cipher_text.resize(end, 0);
     }
 
+    let section = &mut cipher_text[offset..end];
+    section.copy_from_slice(new_text);
+
+    key_stream.seek(offset as u64);
+    key_stream.apply(section);
+
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::aes::{remove_padding, add_padding, encrypt_aes_cbc, decrypt_aes_cbc};
+    use crate::aes::{encrypt_aes_cbc_iv_prefixed, decrypt_aes_cbc_iv_prefixed, edit_aes_ctr, encrypt_aes_ctr, decrypt_aes_ctr, encrypt_aes_cbc, decrypt_aes_cbc, Aes, Aes128, encrypt_cbc, decrypt_cbc, encrypt_ecb, encrypt_ecb_in_place, decrypt_ecb_in_place, encrypt_cbc_in_place, decrypt_cbc_in_place, encrypt_aes_ecb, decrypt_aes_ecb, encrypt_aes_ecb_no_padding, decrypt_aes_ecb_no_padding};
+    #[cfg(target_arch = "x86_64")]
+    use crate::aes::{has_aes_ni, KeySchedule};
+    #[cfg(target_arch = "x86_64")]
+    use crate::aes_soft;
+    use crate::base64::from_base64;
+    use crate::file::file_to_buf;
+    use crate::hex::parse_hex;
+    use crate::string::trim_and_join;
+    use crate::modes::{CounterLayout, cfb_encrypt, cfb_decrypt, ofb_encrypt, ctr_encrypt};
+    use crate::random::random_key;
     use crate::Error;
+    use crate::padding::{Padding, PaddingError};
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::convert::TryInto;
+    use std::str::from_utf8;
+
+    /// Counts the allocations made on each thread, so a test can check that
+    /// the in-place functions don't make any. Tests run on their own
+    /// threads and don't see each other's allocations.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations() -> usize {
+        ALLOCATIONS.with(|a| a.get())
+    }
+
+    /// xorshift, so the differential tests are reproducible
+    fn next_random(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    fn random_block(state: &mut u64) -> [u8; 16] {
+        let mut block = [0u8; 16];
+        block[0..8].copy_from_slice(&next_random(state).to_le_bytes());
+        block[8..16].copy_from_slice(&next_random(state).to_le_bytes());
+        block
+    }
 
     #[test]
     fn cbc_loop() {
@@ -216,81 +393,415 @@ mod tests {
     }
 
     #[test]
-    fn remove_padding_test_valid() {
-        for length in 0..17 {
-            let a = vec![length as u8; length];
+    fn fips_197_appendix_c1() {
+        let key: [u8; 16] = parse_hex("000102030405060708090a0b0c0d0e0f").unwrap().try_into().unwrap();
+        let plain: [u8; 16] = parse_hex("00112233445566778899aabbccddeeff").unwrap().try_into().unwrap();
+        let cipher = Aes::new(&key).unwrap();
+
+        let mut block = plain;
+        cipher.encrypt_block(&mut block);
+        assert_eq!(parse_hex("69c4e0d86a7b0430d8cdb78070b4c55a").unwrap(), block.to_vec());
+
+        cipher.decrypt_block(&mut block);
+        assert_eq!(plain, block);
+    }
+
+    #[test]
+    fn fips_197_appendix_c2() {
+        let key = parse_hex("000102030405060708090a0b0c0d0e0f1011121314151617").unwrap();
+        let plain: [u8; 16] = parse_hex("00112233445566778899aabbccddeeff").unwrap().try_into().unwrap();
+        let cipher = Aes::new(&key).unwrap();
+
+        let mut block = plain;
+        cipher.encrypt_block(&mut block);
+        assert_eq!(parse_hex("dda97ca4864cdfe06eaf70a0ec0d7191").unwrap(), block.to_vec());
+
+        cipher.decrypt_block(&mut block);
+        assert_eq!(plain, block);
+    }
+
+    #[test]
+    fn fips_197_appendix_c3() {
+        let key = parse_hex("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f").unwrap();
+        let plain: [u8; 16] = parse_hex("00112233445566778899aabbccddeeff").unwrap().try_into().unwrap();
+        let cipher = Aes::new(&key).unwrap();
+
+        let mut block = plain;
+        cipher.encrypt_block(&mut block);
+        assert_eq!(parse_hex("8ea2b7ca516745bfeafc49904b496089").unwrap(), block.to_vec());
+
+        cipher.decrypt_block(&mut block);
+        assert_eq!(plain, block);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn multi_block_backends_agree() {
+        if !has_aes_ni() {
+            return;
+        }
+
+        let mut state = 0x9e3779b97f4a7c15;
+        for blocks in 0..20 {
+            let key = random_block(&mut state);
+            let ni = Aes::new(&key).unwrap();
+            let soft = Aes { key_schedule: KeySchedule::Soft(aes_soft::KeySchedule::new(&key)) };
+
+            let mut plain = vec![];
+            for _ in 0..blocks {
+                plain.extend(&random_block(&mut state));
+            }
+
+            let mut ni_buf = plain.clone();
+            let mut soft_buf = plain.clone();
+            ni.encrypt_blocks(&mut ni_buf);
+            soft.encrypt_blocks(&mut soft_buf);
+            assert_eq!(ni_buf, soft_buf);
+
+            let mut single = plain.clone();
+            for block in single.chunks_exact_mut(16) {
+                ni.encrypt_block(block.try_into().unwrap());
+            }
+            assert_eq!(single, ni_buf);
+
+            ni.decrypt_blocks(&mut ni_buf);
+            soft.decrypt_blocks(&mut soft_buf);
+            assert_eq!(plain, ni_buf);
+            assert_eq!(plain, soft_buf);
+        }
+    }
+
+    #[test]
+    fn aes128_reused_for_many_messages() {
+        let key = [b'a', b'b', b'c', b'd', b'e', b'f', b'g', b'h', b'i', b'j', b'k', b'l', b'm', b'o', b'p', b'q'];
+        let cipher = Aes128::new(&key);
+
+        for length in 0..40 {
+            let clean = vec![b'z'; length];
+            let iv = [length as u8; 16];
+
+            let enc = encrypt_cbc(&cipher, &clean, &iv, Padding::Pkcs7).unwrap();
+            assert_eq!(encrypt_aes_cbc(&clean, &key, &iv).unwrap(), enc);
+
+            assert_eq!(clean, decrypt_cbc(&cipher, &enc, &iv, Padding::Pkcs7).unwrap());
+        }
+    }
+
+    #[test]
+    fn wrong_key_size() {
+        for size in &[0, 8, 15, 17, 31, 33] {
+            let result = encrypt_aes_ecb(b"data", &vec![0; *size]);
+
+            assert!(matches!(result.err().unwrap(), Error::Generic("key must be 16, 24 or 32 bytes")));
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn backends_agree() {
+        if !has_aes_ni() {
+            return;
+        }
+
+        let mut state = 0x2545f4914f6cdd1d;
+        for i in 0..3000 {
+            let key_size = [16, 24, 32][i % 3];
+            let mut key = random_block(&mut state).to_vec();
+            key.extend(&random_block(&mut state));
+            key.truncate(key_size);
+            let plain = random_block(&mut state);
+            let ni = Aes::new(&key).unwrap();
+            let soft = Aes { key_schedule: KeySchedule::Soft(aes_soft::KeySchedule::new(&key)) };
+            assert!(matches!(ni.key_schedule, KeySchedule::AesNi(_)));
+
+            let mut ni_block = plain;
+            let mut soft_block = plain;
+            ni.encrypt_block(&mut ni_block);
+            soft.encrypt_block(&mut soft_block);
+            assert_eq!(ni_block, soft_block);
+
+            ni.decrypt_block(&mut ni_block);
+            soft.decrypt_block(&mut soft_block);
+            assert_eq!(plain, ni_block);
+            assert_eq!(plain, soft_block);
+        }
+    }
+
+    const SP800_38A_KEY: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+    const SP800_38A_PLAIN: &str = "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e5130c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710";
+
+    #[test]
+    fn ecb_sp800_38a_encrypt() {
+        let key: [u8; 16] = parse_hex(SP800_38A_KEY).unwrap().try_into().unwrap();
+
+        let enc = encrypt_aes_ecb_no_padding(&parse_hex(SP800_38A_PLAIN).unwrap(), &key).unwrap();
+
+        assert_eq!(parse_hex("3ad77bb40d7a3660a89ecaf32466ef97f5d3d58503b9699de785895a96fdbaaf43b1cd7f598ece23881b00e3ed0306887b0c785e27e8ad3f8223207104725dd4").unwrap(), enc);
+    }
+
+    #[test]
+    fn ecb_sp800_38a_decrypt() {
+        let key: [u8; 16] = parse_hex(SP800_38A_KEY).unwrap().try_into().unwrap();
+
+        let dec = decrypt_aes_ecb_no_padding(&parse_hex("3ad77bb40d7a3660a89ecaf32466ef97f5d3d58503b9699de785895a96fdbaaf43b1cd7f598ece23881b00e3ed0306887b0c785e27e8ad3f8223207104725dd4").unwrap(), &key).unwrap();
+
+        assert_eq!(parse_hex(SP800_38A_PLAIN).unwrap(), dec);
+    }
+
+    #[test]
+    fn ecb_sp800_38a_aes192() {
+        let key = parse_hex("8e73b0f7da0e6452c810f32b809079e562f8ead2522c6b7b").unwrap();
+        let cipher = parse_hex("bd334f1d6e45f25ff712a214571fa5cc974104846d0ad3ad7734ecb3ecee4eefef7afd2270e2e60adce0ba2face6444e9a4b41ba738d6c72fb16691603c18e0e").unwrap();
 
-            let mut a_padded = a.clone();
-            a_padded.append(&mut vec![16 - (length % 16) as u8; 16 - (length % 16)]);
+        assert_eq!(cipher, encrypt_aes_ecb_no_padding(&parse_hex(SP800_38A_PLAIN).unwrap(), &key).unwrap());
+        assert_eq!(parse_hex(SP800_38A_PLAIN).unwrap(), decrypt_aes_ecb_no_padding(&cipher, &key).unwrap());
+    }
+
+    #[test]
+    fn ecb_sp800_38a_aes256() {
+        let key = parse_hex("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4").unwrap();
+        let cipher = parse_hex("f3eed1bdb5d2a03c064b5a7e3db181f8591ccb10d410ed26dc5ba74a31362870b6ed21b99ca6f4f9f153e7b1beafed1d23304b7a39f9f3ff067d8d8f9e24ecc7").unwrap();
+
+        assert_eq!(cipher, encrypt_aes_ecb_no_padding(&parse_hex(SP800_38A_PLAIN).unwrap(), &key).unwrap());
+        assert_eq!(parse_hex(SP800_38A_PLAIN).unwrap(), decrypt_aes_ecb_no_padding(&cipher, &key).unwrap());
+    }
+
+    #[test]
+    fn cbc_sp800_38a_first_block() {
+        let iv: [u8; 16] = parse_hex("000102030405060708090a0b0c0d0e0f").unwrap().try_into().unwrap();
+        let plain = parse_hex(SP800_38A_PLAIN).unwrap();
+        let cases = [
+            (SP800_38A_KEY, "7649abac8119b246cee98e9b12e9197d"),
+            ("8e73b0f7da0e6452c810f32b809079e562f8ead2522c6b7b", "4f021db243bc633d7178183a9fa071e8"),
+            ("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4", "f58c4c04d6e5f1ba779eabfb5f7bfbd6"),
+        ];
+
+        for (key, first_block) in cases.iter() {
+            let key = parse_hex(key).unwrap();
+
+            let enc = encrypt_aes_cbc(&plain, &key, &iv).unwrap();
+            assert_eq!(parse_hex(first_block).unwrap(), enc[..16].to_vec());
+
+            assert_eq!(plain, decrypt_aes_cbc(&enc, &key, &iv).unwrap());
+        }
+    }
+
+    #[test]
+    fn ecb_padded_adds_full_block() {
+        let key: [u8; 16] = parse_hex(SP800_38A_KEY).unwrap().try_into().unwrap();
+        let plain = parse_hex(SP800_38A_PLAIN).unwrap();
+
+        let enc = encrypt_aes_ecb(&plain, &key).unwrap();
+
+        assert_eq!(80, enc.len());
+        assert_eq!(encrypt_aes_ecb_no_padding(&plain, &key).unwrap(), enc[..64].to_vec());
+        assert_eq!(plain, decrypt_aes_ecb(&enc, &key).unwrap());
+    }
 
-            remove_padding(&mut a_padded).unwrap();
+    #[test]
+    fn ecb_loop() {
+        let key = [b'a', b'b', b'c', b'd', b'e', b'f', b'g', b'h', b'i', b'j', b'k', b'l', b'm', b'o', b'p', b'q'];
+
+        for length in 0..50 {
+            let clean = vec![b'x'; length];
+
+            let enc = encrypt_aes_ecb(&clean, &key).unwrap();
+            assert_eq!(0, enc.len() % 16);
 
-            assert_eq!(a, a_padded);
+            assert_eq!(clean, decrypt_aes_ecb(&enc, &key).unwrap());
         }
     }
 
     #[test]
-    fn add_and_remove_padding_test_valid() {
-        for length in 0..17 {
-            let a = vec![length as u8; length];
+    fn ecb_no_padding_wrong_length() {
+        let key = [0; 16];
+
+        let result = encrypt_aes_ecb_no_padding(&[0; 17], &key);
+
+        assert!(matches!(result.err().unwrap(), Error::Generic("block size isn't 16")));
+    }
+
+    #[test]
+    fn ctr_edit_matches_fresh_encryption() {
+        let key = [7; 16];
+        let layout = CounterLayout::LittleEndian64(3);
+        let original = b"The quick brown fox jumps over the lazy dog, twice over".to_vec();
 
-            let mut a_padded = a.clone();
-            add_padding(&mut a_padded, 16).unwrap();
+        for offset in &[0, 5, 16, 20, 40] {
+            let mut cipher_text = encrypt_aes_ctr(&original, &key, &layout).unwrap();
+            edit_aes_ctr(&mut cipher_text, &key, &layout, *offset, b"CAT").unwrap();
 
-            remove_padding(&mut a_padded).unwrap();
+            let mut expected = original.clone();
+            expected[*offset..*offset + 3].copy_from_slice(b"CAT");
 
-            assert_eq!(a, a_padded);
+            assert_eq!(encrypt_aes_ctr(&expected, &key, &layout).unwrap(), cipher_text);
         }
     }
 
     #[test]
-    fn remove_padding_test_empty_buf() {
-        let mut a = vec![];
+    fn ctr_edit_extends() {
+        let key = [7; 16];
+        let layout = CounterLayout::LittleEndian64(3);
 
-        let result = remove_padding(&mut a);
+        let mut cipher_text = encrypt_aes_ctr(b"hello", &key, &layout).unwrap();
+        edit_aes_ctr(&mut cipher_text, &key, &layout, 5, b" world").unwrap();
 
-        let result = result.err().unwrap();
-        assert!(matches!(result, Error::Generic("empty buffer, not padded")));
+        assert_eq!(b"hello world".to_vec(), decrypt_aes_ctr(&cipher_text, &key, &layout).unwrap());
     }
 
     #[test]
-    fn remove_padding_test_wrong_length() {
-        let mut a = vec![3; 5];
+    fn ctr_edit_offset_past_end() {
+        let key = [7; 16];
+        let layout = CounterLayout::LittleEndian64(3);
 
-        let result = remove_padding(&mut a);
+        let mut cipher_text = encrypt_aes_ctr(b"hello", &key, &layout).unwrap();
+        let result = edit_aes_ctr(&mut cipher_text, &key, &layout, 6, b"x");
 
-        let result = result.err().unwrap();
-        assert!(matches!(result, Error::Generic("buffer not padded to 16")));
+        assert!(matches!(result.err().unwrap(), Error::Generic("offset is past the end of the cipher text")));
     }
 
     #[test]
-    fn remove_padding_test_not_padded() {
-        let mut a = vec![20; 16];
+    fn cbc_detached_iv_random_ivs() {
+        let key = random_key();
 
-        let result = remove_padding(&mut a);
+        for length in &[0, 1, 15, 16, 17, 31, 32, 33, 100] {
+            let clean = vec![b'm'; *length];
+            let iv = random_key();
 
-        let result = result.err().unwrap();
-        assert!(matches!(result, Error::Generic("pad byte wrong")));
+            let enc = encrypt_aes_cbc(&clean, &key, &iv).unwrap();
+            assert_eq!((length / 16 + 1) * 16, enc.len());
+
+            assert_eq!(clean, decrypt_aes_cbc(&enc, &key, &iv).unwrap());
+        }
     }
 
     #[test]
-    fn remove_padding_test_not_padded_zero() {
-        let mut a = vec![0; 16];
+    fn cbc_iv_prefixed_random_ivs() {
+        let key = random_key();
 
-        let result = remove_padding(&mut a);
+        for length in &[0, 1, 15, 16, 17, 31, 32, 33, 100] {
+            let clean = vec![b'm'; *length];
+            let iv = random_key();
 
-        let result = result.err().unwrap();
-        assert!(matches!(result, Error::Generic("pad byte wrong")));
+            let enc = encrypt_aes_cbc_iv_prefixed(&clean, &key, &iv).unwrap();
+            assert_eq!((length / 16 + 2) * 16, enc.len());
+            assert_eq!(iv.to_vec(), enc[..16].to_vec());
+            assert_eq!(encrypt_aes_cbc(&clean, &key, &iv).unwrap(), enc[16..].to_vec());
+
+            assert_eq!(clean, decrypt_aes_cbc_iv_prefixed(&enc, &key).unwrap());
+        }
     }
 
     #[test]
-    fn remove_padding_test_not_padded_matching_last_byte() {
-        let mut a = vec![3; 16];
-        a[15] = 5;
+    fn cbc_iv_prefixed_challenge_10() {
+        let key = b"YELLOW SUBMARINE";
+        let b64 = file_to_buf("res/10.txt").unwrap();
+        let detached = from_base64(&trim_and_join(from_utf8(&b64).unwrap())).unwrap();
+        let mut framed = vec![0; 16];
+        framed.extend(&detached);
 
-        let result = remove_padding(&mut a);
+        let plain_text = decrypt_aes_cbc_iv_prefixed(&framed, key).unwrap();
 
-        let result = result.err().unwrap();
-        assert!(matches!(result, Error::Generic("pad byte wrong")));
+        assert_eq!(decrypt_aes_cbc(&detached, key, &[0; 16]).unwrap(), plain_text);
+        assert_eq!(framed, encrypt_aes_cbc_iv_prefixed(&plain_text, key, &[0; 16]).unwrap());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn cbc_passes_padding_errors_through() {
+        let key = [9; 16];
+        let iv = [3; 16];
+        let cipher = Aes::new(&key).unwrap();
+        let reason = |plain: &[u8]| {
+            let enc = encrypt_cbc(&cipher, plain, &iv, Padding::None).unwrap();
+            match decrypt_aes_cbc(&enc, &key, &iv) {
+                Err(Error::PaddingError(reason)) => reason,
+                _ => panic!("expected a padding error"),
+            }
+        };
+
+        let mut plain = [0u8; 16];
+        assert_eq!(PaddingError::InvalidPadByte, reason(&plain));
+        plain[15] = 2;
+        assert_eq!(PaddingError::InconsistentPadBytes, reason(&plain));
+    }
+
+    #[test]
+    fn cbc_iv_prefixed_missing_iv() {
+        let result = decrypt_aes_cbc_iv_prefixed(&[0; 15], &[0; 16]);
+
+        assert!(matches!(result.err().unwrap(), Error::Generic("cipher text is missing the iv")));
+    }
+
+    #[test]
+    fn in_place_matches_allocating() {
+        let cipher = Aes::new(b"YELLOW SUBMARINE").unwrap();
+        let iv = [7u8; 16];
+
+        for padding in [Padding::Pkcs7, Padding::AnsiX923, Padding::Iso7816].iter() {
+            for length in 0..50 {
+                let plain: Vec<u8> = (0..length as u8).collect();
+                let mut buf = [0u8; 64];
+                buf[..length].copy_from_slice(&plain);
+
+                let len = encrypt_ecb_in_place(&cipher, *padding, &mut buf, length).unwrap();
+                assert_eq!(encrypt_ecb(&cipher, &plain, *padding).unwrap(), buf[..len].to_vec());
+                let len = decrypt_ecb_in_place(&cipher, *padding, &mut buf[..len]).unwrap();
+                assert_eq!(plain, buf[..len].to_vec());
+
+                let len = encrypt_cbc_in_place(&cipher, &iv, *padding, &mut buf, length).unwrap();
+                assert_eq!(encrypt_cbc(&cipher, &plain, &iv, *padding).unwrap(), buf[..len].to_vec());
+                let len = decrypt_cbc_in_place(&cipher, &iv, *padding, &mut buf[..len]).unwrap();
+                assert_eq!(plain, buf[..len].to_vec());
+            }
+        }
+    }
+
+    #[test]
+    fn in_place_does_not_allocate() {
+        let cipher = Aes::new(b"YELLOW SUBMARINE").unwrap();
+        let iv = [7u8; 16];
+        let mut buf = [0u8; 16 * 40];
+        let paddings = [Padding::None, Padding::Pkcs7, Padding::AnsiX923, Padding::Iso7816, Padding::Iso10126, Padding::Zero];
+
+        let before = allocations();
+        for padding in paddings.iter() {
+            let len = encrypt_ecb_in_place(&cipher, *padding, &mut buf, 16 * 30).unwrap();
+            decrypt_ecb_in_place(&cipher, *padding, &mut buf[..len]).unwrap();
+
+            let len = encrypt_cbc_in_place(&cipher, &iv, *padding, &mut buf, 16 * 30).unwrap();
+            decrypt_cbc_in_place(&cipher, &iv, *padding, &mut buf[..len]).unwrap();
+        }
+        cfb_encrypt(&cipher, &iv, &mut buf[..100]).unwrap();
+        cfb_decrypt(&cipher, &iv, &mut buf[..100]).unwrap();
+        ofb_encrypt(&cipher, &iv, &mut buf[..100]).unwrap();
+        ctr_encrypt(&cipher, &CounterLayout::LittleEndian64(0), &mut buf[..100]).unwrap();
+
+        assert_eq!(before, allocations());
+    }
+
+    #[test]
+    fn in_place_needs_room_for_padding() {
+        let cipher = Aes::new(b"YELLOW SUBMARINE").unwrap();
+        let mut buf = [0u8; 16];
+
+        let result = encrypt_ecb_in_place(&cipher, Padding::Pkcs7, &mut buf, 16);
+        assert!(matches!(result.err().unwrap(), Error::Generic("buffer has no room for the padding")));
+
+        assert_eq!(16, encrypt_ecb_in_place(&cipher, Padding::None, &mut buf, 16).unwrap());
+    }
+
+    #[test]
+    fn cbc_without_padding_is_raw_blocks() {
+        let cipher = Aes::new(b"YELLOW SUBMARINE").unwrap();
+        let iv = [7u8; 16];
+        let plain = [5u8; 32];
+
+        let enc = encrypt_cbc(&cipher, &plain, &iv, Padding::None).unwrap();
+        assert_eq!(32, enc.len());
+        assert_eq!(plain.to_vec(), decrypt_cbc(&cipher, &enc, &iv, Padding::None).unwrap());
+
+        let with_zeros = encrypt_cbc(&cipher, &plain[..20], &iv, Padding::Zero).unwrap();
+        assert_eq!(enc[..16], with_zeros[..16]);
+        assert_eq!(plain[..20].to_vec(), decrypt_cbc(&cipher, &with_zeros, &iv, Padding::Zero).unwrap());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::aes_soft;
+use std::convert::TryInto;
+use std::arch::x86_64::{__m128i, _mm_loadu_si128, _mm_xor_si128, _mm_aesdec_si128, _mm_aesdeclast_si128, _mm_storeu_si128, _mm_aeskeygenassist_si128, _mm_shuffle_epi32, _mm_slli_si128, _mm_aesimc_si128, _mm_setzero_si128, _mm_aesenc_si128, _mm_aesenclast_si128};
+
+/// copied here from sse.rs so that we can run this in stable rust
+#[inline]
+#[allow(non_snake_case)]
+const fn _MM_SHUFFLE(z: u32, y: u32, x: u32, w: u32) -> i32 {
+    ((z << 6) | (y << 4) | (x << 2) | w) as i32
+}
+
+/// Expanded AES key, the decryption keys are stored in the order the
+/// equivalent inverse cipher uses them.
+pub struct KeySchedule {
+    enc: [__m128i; 15],
+    dec: [__m128i; 15],
+    rounds: usize,
+}
+
+#[target_feature(enable = "aes")]
+unsafe fn aes_128_key_expansion(key: __m128i, keygened: __m128i) -> __m128i {
+    let keygened = _mm_shuffle_epi32(keygened, _MM_SHUFFLE(3,3,3,3));
+    let key = _mm_xor_si128(key, _mm_slli_si128(key, 4));
+    let key = _mm_xor_si128(key, _mm_slli_si128(key, 4));
+    let key = _mm_xor_si128(key, _mm_slli_si128(key, 4));
+
+    _mm_xor_si128(key, keygened)
+}
+
+#[target_feature(enable = "aes")]
+unsafe fn aes128_load_key(enc_key: &[u8], key_schedule: &mut [__m128i; 15]) {
+    key_schedule[0] = _mm_loadu_si128(enc_key.as_ptr() as *const __m128i);
+    key_schedule[1]  = aes_128_key_expansion(key_schedule[0], _mm_aeskeygenassist_si128(key_schedule[0], 0x01));
+    key_schedule[2]  = aes_128_key_expansion(key_schedule[1], _mm_aeskeygenassist_si128(key_schedule[1], 0x02));
+    key_schedule[3]  = aes_128_key_expansion(key_schedule[2], _mm_aeskeygenassist_si128(key_schedule[2], 0x04));
+    key_schedule[4]  = aes_128_key_expansion(key_schedule[3], _mm_aeskeygenassist_si128(key_schedule[3], 0x08));
+    key_schedule[5]  = aes_128_key_expansion(key_schedule[4], _mm_aeskeygenassist_si128(key_schedule[4], 0x10));
+    key_schedule[6]  = aes_128_key_expansion(key_schedule[5], _mm_aeskeygenassist_si128(key_schedule[5], 0x20));
+    key_schedule[7]  = aes_128_key_expansion(key_schedule[6], _mm_aeskeygenassist_si128(key_schedule[6], 0x40));
+    key_schedule[8]  = aes_128_key_expansion(key_schedule[7], _mm_aeskeygenassist_si128(key_schedule[7], 0x80));
+    key_schedule[9]  = aes_128_key_expansion(key_schedule[8], _mm_aeskeygenassist_si128(key_schedule[8], 0x1B));
+    key_schedule[10] = aes_128_key_expansion(key_schedule[9], _mm_aeskeygenassist_si128(key_schedule[9], 0x36));
+}
+
+/// The 192 and 256 bit schedules don't line up with the 128 bit registers the
+/// way the 128 bit one does, so those are expanded in software and loaded.
+#[target_feature(enable = "aes")]
+unsafe fn load_expanded_key(enc_key: &[u8], key_schedule: &mut [__m128i; 15]) -> usize {
+    let (w, rounds) = aes_soft::expand_key(enc_key);
+
+    for (round, key) in key_schedule.iter_mut().enumerate().take(rounds + 1) {
+        let mut bytes = [0u8; 16];
+        for col in 0..4 {
+            bytes[col * 4..col * 4 + 4].copy_from_slice(&w[round * 4 + col].to_be_bytes());
+        }
+        *key = _mm_loadu_si128(bytes.as_ptr() as *const __m128i);
+    }
+
+    rounds
+}
+
+impl KeySchedule {
+    /// Callers must have checked that the cpu supports AES-NI and that the
+    /// key is 16, 24 or 32 bytes.
+    #[target_feature(enable = "aes")]
+    pub unsafe fn new(key: &[u8]) -> KeySchedule {
+        let mut enc: [__m128i; 15] = [_mm_setzero_si128(); 15];
+
+        let rounds = match key.len() {
+            16 => {
+                aes128_load_key(key, &mut enc);
+                10
+            },
+            _ => load_expanded_key(key, &mut enc),
+        };
+
+        let mut dec: [__m128i; 15] = [_mm_setzero_si128(); 15];
+        dec[0] = enc[rounds];
+        for round in 1..rounds {
+            dec[round] = _mm_aesimc_si128(enc[rounds - round]);
+        }
+        dec[rounds] = enc[0];
+
+        KeySchedule { enc, dec, rounds }
+    }
+}
+
+/// Callers must have checked that the cpu supports AES-NI.
+#[target_feature(enable = "aes")]
+pub unsafe fn encrypt_block(key_schedule: &KeySchedule, block: &mut [u8; 16]) {
+    let rounds = key_schedule.rounds;
+    let key_schedule = &key_schedule.enc;
+    let mut m = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+
+    m = _mm_xor_si128(m, key_schedule[0]);
+    for key in &key_schedule[1..rounds] {
+        m = _mm_aesenc_si128(m, *key);
+    }
+    m = _mm_aesenclast_si128(m, key_schedule[rounds]);
+
+    _mm_storeu_si128(block.as_mut_ptr() as *mut __m128i, m);
+}
+
+/// Callers must have checked that the cpu supports AES-NI.
+#[target_feature(enable = "aes")]
+pub unsafe fn decrypt_block(key_schedule: &KeySchedule, block: &mut [u8; 16]) {
+    let rounds = key_schedule.rounds;
+    let key_schedule = &key_schedule.dec;
+    let mut m = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+
+    m = _mm_xor_si128(m, key_schedule[0]);
+    for key in &key_schedule[1..rounds] {
+        m = _mm_aesdec_si128(m, *key);
+    }
+    m = _mm_aesdeclast_si128(m, key_schedule[rounds]);
+
+    _mm_storeu_si128(block.as_mut_ptr() as *mut __m128i, m);
+}
+
+/// How many independent blocks are kept in flight, aesenc has a latency of
+/// several cycles but the cpu can start a new one every cycle.
+const PARALLEL_BLOCKS: usize = 8;
+
+/// buf must be a whole number of blocks.
+/// Callers must have checked that the cpu supports AES-NI.
+#[target_feature(enable = "aes")]
+pub unsafe fn encrypt_blocks(key_schedule: &KeySchedule, buf: &mut [u8]) {
+    let rounds = key_schedule.rounds;
+    let keys = &key_schedule.enc;
+
+    let mut chunks = buf.chunks_exact_mut(16 * PARALLEL_BLOCKS);
+    for chunk in &mut chunks {
+        let ptr = chunk.as_mut_ptr() as *mut __m128i;
+        let mut m = [_mm_setzero_si128(); PARALLEL_BLOCKS];
+
+        for (i, m) in m.iter_mut().enumerate() {
+            *m = _mm_xor_si128(_mm_loadu_si128(ptr.add(i)), keys[0]);
+        }
+        for key in &keys[1..rounds] {
+            for m in m.iter_mut() {
+                *m = _mm_aesenc_si128(*m, *key);
+            }
+        }
+        for (i, m) in m.iter().enumerate() {
+            _mm_storeu_si128(ptr.add(i), _mm_aesenclast_si128(*m, keys[rounds]));
+        }
+    }
+
+    for block in chunks.into_remainder().chunks_exact_mut(16) {
+        encrypt_block(key_schedule, block.try_into().unwrap());
+    }
+}
+
+/// buf must be a whole number of blocks.
+/// Callers must have checked that the cpu supports AES-NI.
+#[target_feature(enable = "aes")]
+pub unsafe fn decrypt_blocks(key_schedule: &KeySchedule, buf: &mut [u8]) {
+    let rounds = key_schedule.rounds;
+    let keys = &key_schedule.dec;
+
+    let mut chunks = buf.chunks_exact_mut(16 * PARALLEL_BLOCKS);
+    for chunk in &mut chunks {
+        let ptr = chunk.as_mut_ptr() as *mut __m128i;
+        let mut m = [_mm_setzero_si128(); PARALLEL_BLOCKS];
+
+        for (i, m) in m.iter_mut().enumerate() {
+            *m = _mm_xor_si128(_mm_loadu_si128(ptr.add(i)), keys[0]);
+        }
+        for key in &keys[1..rounds] {
+            for m in m.iter_mut() {
+                *m = _mm_aesdec_si128(*m, *key);
+            }
+        }
+        for (i, m) in m.iter().enumerate() {
+            _mm_storeu_si128(ptr.add(i), _mm_aesdeclast_si128(*m, keys[rounds]));
+        }
+    }
+
+    for block in chunks.into_remainder().chunks_exact_mut(16) {
+        decrypt_block(key_schedule, block.try_into().unwrap());
+    }
+}
\ No newline at end of file
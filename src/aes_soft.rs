@@ -0,0 +1,263 @@
+// Table driven AES, used when the cpu doesn't have AES-NI.
+//
+// The state is kept as four big endian column words, and the tables fold
+// SubBytes, ShiftRows and MixColumns into one lookup per byte.
+
+const RCON: [u32; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+const fn xtime(x: u8) -> u8 {
+    (x << 1) ^ if x & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+const fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    product
+}
+
+/// walks the multiplicative group with generator 3, p is 3^i and q is 3^-i
+const fn make_sbox() -> [u8; 256] {
+    let mut sbox = [0u8; 256];
+    let mut p: u8 = 1;
+    let mut q: u8 = 1;
+    loop {
+        p = p ^ (p << 1) ^ if p & 0x80 != 0 { 0x1b } else { 0 };
+
+        q ^= q << 1;
+        q ^= q << 2;
+        q ^= q << 4;
+        if q & 0x80 != 0 {
+            q ^= 0x09;
+        }
+
+        sbox[p as usize] = q ^ q.rotate_left(1) ^ q.rotate_left(2) ^ q.rotate_left(3) ^ q.rotate_left(4) ^ 0x63;
+
+        if p == 1 {
+            break;
+        }
+    }
+    sbox[0] = 0x63;
+    sbox
+}
+
+const fn make_inv_sbox(sbox: &[u8; 256]) -> [u8; 256] {
+    let mut inv = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        inv[sbox[i] as usize] = i as u8;
+        i += 1;
+    }
+    inv
+}
+
+const fn word(b0: u8, b1: u8, b2: u8, b3: u8) -> u32 {
+    (b0 as u32) << 24 | (b1 as u32) << 16 | (b2 as u32) << 8 | b3 as u32
+}
+
+/// the MixColumns column for a byte in row 0, other rows are rotations of it
+const fn make_te(sbox: &[u8; 256]) -> [u32; 256] {
+    let mut te = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let s = sbox[i];
+        te[i] = word(mul(s, 2), s, s, mul(s, 3));
+        i += 1;
+    }
+    te
+}
+
+/// the InvMixColumns column for a byte in row 0, other rows are rotations of it
+const fn make_td(inv_sbox: &[u8; 256]) -> [u32; 256] {
+    let mut td = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let s = inv_sbox[i];
+        td[i] = word(mul(s, 14), mul(s, 9), mul(s, 13), mul(s, 11));
+        i += 1;
+    }
+    td
+}
+
+const SBOX: [u8; 256] = make_sbox();
+const INV_SBOX: [u8; 256] = make_inv_sbox(&SBOX);
+const TE: [u32; 256] = make_te(&SBOX);
+const TD: [u32; 256] = make_td(&INV_SBOX);
+
+fn sub_word(w: u32) -> u32 {
+    word(SBOX[(w >> 24) as usize], SBOX[(w >> 16) as usize & 0xff], SBOX[(w >> 8) as usize & 0xff], SBOX[w as usize & 0xff])
+}
+
+fn inv_mix_column(w: u32) -> u32 {
+    TD[SBOX[(w >> 24) as usize] as usize]
+        ^ TD[SBOX[(w >> 16) as usize & 0xff] as usize].rotate_right(8)
+        ^ TD[SBOX[(w >> 8) as usize & 0xff] as usize].rotate_right(16)
+        ^ TD[SBOX[w as usize & 0xff] as usize].rotate_right(24)
+}
+
+/// Runs the FIPS-197 key expansion for a 16, 24 or 32 byte key, the caller
+/// is responsible for checking the key length.
+pub fn expand_key(key: &[u8]) -> ([u32; 60], usize) {
+    let nk = key.len() / 4;
+    let rounds = nk + 6;
+
+    let mut w = [0u32; 60];
+    for i in 0..nk {
+        w[i] = word(key[i * 4], key[i * 4 + 1], key[i * 4 + 2], key[i * 4 + 3]);
+    }
+    for i in nk..(rounds + 1) * 4 {
+        let mut temp = w[i - 1];
+        if i % nk == 0 {
+            temp = sub_word(temp.rotate_left(8)) ^ (RCON[i / nk - 1] << 24);
+        } else if nk > 6 && i % nk == 4 {
+            temp = sub_word(temp);
+        }
+        w[i] = w[i - nk] ^ temp;
+    }
+
+    (w, rounds)
+}
+
+/// Expanded AES key, decryption keys are in the order of the equivalent
+/// inverse cipher so both directions can use the same round structure.
+pub struct KeySchedule {
+    enc: [u32; 60],
+    dec: [u32; 60],
+    rounds: usize,
+}
+
+impl KeySchedule {
+    pub fn new(key: &[u8]) -> KeySchedule {
+        let (enc, rounds) = expand_key(key);
+
+        let mut dec = [0u32; 60];
+        for round in 0..=rounds {
+            for col in 0..4 {
+                let w = enc[(rounds - round) * 4 + col];
+                dec[round * 4 + col] = if round == 0 || round == rounds {
+                    w
+                } else {
+                    inv_mix_column(w)
+                };
+            }
+        }
+
+        KeySchedule { enc, dec, rounds }
+    }
+}
+
+fn load_block(block: &[u8; 16], round_key: &[u32]) -> [u32; 4] {
+    let mut s = [0u32; 4];
+    for (i, s) in s.iter_mut().enumerate() {
+        *s = word(block[i * 4], block[i * 4 + 1], block[i * 4 + 2], block[i * 4 + 3]) ^ round_key[i];
+    }
+    s
+}
+
+fn store_block(s: &[u32; 4], block: &mut [u8; 16]) {
+    for (i, w) in s.iter().enumerate() {
+        block[i * 4..i * 4 + 4].copy_from_slice(&w.to_be_bytes());
+    }
+}
+
+pub fn encrypt_block(key_schedule: &KeySchedule, block: &mut [u8; 16]) {
+    let rk = &key_schedule.enc;
+    let rounds = key_schedule.rounds;
+    let mut s = load_block(block, &rk[0..4]);
+
+    for round in 1..rounds {
+        let mut t = [0u32; 4];
+        for j in 0..4 {
+            t[j] = TE[(s[j] >> 24) as usize]
+                ^ TE[(s[(j + 1) % 4] >> 16) as usize & 0xff].rotate_right(8)
+                ^ TE[(s[(j + 2) % 4] >> 8) as usize & 0xff].rotate_right(16)
+                ^ TE[s[(j + 3) % 4] as usize & 0xff].rotate_right(24)
+                ^ rk[round * 4 + j];
+        }
+        s = t;
+    }
+
+    let mut t = [0u32; 4];
+    for j in 0..4 {
+        t[j] = word(SBOX[(s[j] >> 24) as usize],
+                    SBOX[(s[(j + 1) % 4] >> 16) as usize & 0xff],
+                    SBOX[(s[(j + 2) % 4] >> 8) as usize & 0xff],
+                    SBOX[s[(j + 3) % 4] as usize & 0xff]) ^ rk[rounds * 4 + j];
+    }
+
+    store_block(&t, block);
+}
+
+pub fn decrypt_block(key_schedule: &KeySchedule, block: &mut [u8; 16]) {
+    let rk = &key_schedule.dec;
+    let rounds = key_schedule.rounds;
+    let mut s = load_block(block, &rk[0..4]);
+
+    for round in 1..rounds {
+        let mut t = [0u32; 4];
+        for j in 0..4 {
+            t[j] = TD[(s[j] >> 24) as usize]
+                ^ TD[(s[(j + 3) % 4] >> 16) as usize & 0xff].rotate_right(8)
+                ^ TD[(s[(j + 2) % 4] >> 8) as usize & 0xff].rotate_right(16)
+                ^ TD[s[(j + 1) % 4] as usize & 0xff].rotate_right(24)
+                ^ rk[round * 4 + j];
+        }
+        s = t;
+    }
+
+    let mut t = [0u32; 4];
+    for j in 0..4 {
+        t[j] = word(INV_SBOX[(s[j] >> 24) as usize],
+                    INV_SBOX[(s[(j + 3) % 4] >> 16) as usize & 0xff],
+                    INV_SBOX[(s[(j + 2) % 4] >> 8) as usize & 0xff],
+                    INV_SBOX[s[(j + 1) % 4] as usize & 0xff]) ^ rk[rounds * 4 + j];
+    }
+
+    store_block(&t, block);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aes_soft::{SBOX, INV_SBOX, KeySchedule, encrypt_block, decrypt_block, expand_key};
+    use crate::hex::parse_hex;
+    use std::convert::TryInto;
+
+    #[test]
+    fn sbox_known_values() {
+        assert_eq!(0x63, SBOX[0x00]);
+        assert_eq!(0x7c, SBOX[0x01]);
+        assert_eq!(0xed, SBOX[0x53]);
+        assert_eq!(0x16, SBOX[0xff]);
+        assert_eq!(0x53, INV_SBOX[0xed]);
+    }
+
+    #[test]
+    fn fips_197_appendix_b() {
+        let key: [u8; 16] = parse_hex("2b7e151628aed2a6abf7158809cf4f3c").unwrap().try_into().unwrap();
+        let plain: [u8; 16] = parse_hex("3243f6a8885a308d313198a2e0370734").unwrap().try_into().unwrap();
+        let key_schedule = KeySchedule::new(&key);
+
+        let mut block = plain;
+        encrypt_block(&key_schedule, &mut block);
+        assert_eq!(parse_hex("3925841d02dc09fbdc118597196a0b32").unwrap(), block.to_vec());
+
+        decrypt_block(&key_schedule, &mut block);
+        assert_eq!(plain, block);
+    }
+
+    #[test]
+    fn fips_197_appendix_a_last_round_keys() {
+        let (w, rounds) = expand_key(&parse_hex("8e73b0f7da0e6452c810f32b809079e562f8ead2522c6b7b").unwrap());
+        assert_eq!(12, rounds);
+        assert_eq!([0xe98ba06f, 0x448c773c, 0x8ecc7204, 0x01002202], w[48..52]);
+
+        let (w, rounds) = expand_key(&parse_hex("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4").unwrap());
+        assert_eq!(14, rounds);
+        assert_eq!([0xfe4890d1, 0xe6188d0b, 0x046df344, 0x706c631e], w[56..60]);
+    }
+}
\ No newline at end of file
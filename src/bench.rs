@@ -0,0 +1,92 @@
+use crate::Error;
+use crate::aes::{Aes, Aes128, encrypt_ecb_in_place, decrypt_ecb_in_place, decrypt_cbc_in_place};
+use crate::modes::{BlockCipher, CounterLayout, cfb_encrypt, cfb_decrypt, ofb_encrypt, ofb_decrypt, ctr_encrypt};
+use crate::padding::Padding;
+use crate::random::random_key;
+use std::time::Instant;
+
+// Throughput of the modes with and without the multi block path, ECB and
+// CBC through the in-place functions without padding like the attacks use
+// them. Run with
+// `cargo run --release bench` to get meaningful numbers. CFB and OFB chain
+// every block on the one before, so they have no multi block path and show
+// what a mode runs at without one.
+
+const BUFFER_SIZE: usize = 16 * 1024 * 1024;
+const REPEATS: usize = 5;
+
+/// Only forwards single blocks, so the modes fall back to the default one
+/// block at a time loop, which is how they ran before the pipelined path.
+struct OneBlockAtATime<'a>(&'a Aes);
+
+impl BlockCipher for OneBlockAtATime<'_> {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        BlockCipher::encrypt_block(self.0, block);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        BlockCipher::decrypt_block(self.0, block);
+    }
+}
+
+fn megabytes_per_second<F>(mut f: F) -> Result<f64, Error>
+    where F: FnMut(&mut [u8]) -> Result<(), Error> {
+    let mut buf = vec![0u8; BUFFER_SIZE];
+
+    // warm up the caches and the page mappings
+    f(&mut buf)?;
+
+    let start = Instant::now();
+    for _ in 0..REPEATS {
+        f(&mut buf)?;
+    }
+    let seconds = start.elapsed().as_secs_f64();
+
+    Ok((BUFFER_SIZE * REPEATS) as f64 / seconds / (1024.0 * 1024.0))
+}
+
+fn report(name: &str, before: f64, after: f64) {
+    println!("{:<14}{:>14.1}{:>14.1}{:>9.2}x", name, before, after, after / before);
+}
+
+pub fn run() -> Result<(), Error> {
+    let aes = Aes128::new(&random_key());
+    let single = OneBlockAtATime(&aes);
+    let iv = [0u8; 16];
+    let layout = CounterLayout::LittleEndian64(0);
+
+    println!("{} MiB buffers, {} repeats", BUFFER_SIZE / (1024 * 1024), REPEATS);
+    println!("{:<14}{:>14}{:>14}{:>10}", "mode", "single MB/s", "batched MB/s", "speedup");
+
+    let len = BUFFER_SIZE;
+    report("ecb encrypt",
+           megabytes_per_second(|buf| encrypt_ecb_in_place(&single, Padding::None, buf, len).map(|_| ()))?,
+           megabytes_per_second(|buf| encrypt_ecb_in_place(&aes, Padding::None, buf, len).map(|_| ()))?);
+    report("ecb decrypt",
+           megabytes_per_second(|buf| decrypt_ecb_in_place(&single, Padding::None, buf).map(|_| ()))?,
+           megabytes_per_second(|buf| decrypt_ecb_in_place(&aes, Padding::None, buf).map(|_| ()))?);
+    report("cbc decrypt",
+           megabytes_per_second(|buf| decrypt_cbc_in_place(&single, &iv, Padding::None, buf).map(|_| ()))?,
+           megabytes_per_second(|buf| decrypt_cbc_in_place(&aes, &iv, Padding::None, buf).map(|_| ()))?);
+    report("cfb encrypt",
+           megabytes_per_second(|buf| cfb_encrypt(&single, &iv, buf))?,
+           megabytes_per_second(|buf| cfb_encrypt(&aes, &iv, buf))?);
+    report("cfb decrypt",
+           megabytes_per_second(|buf| cfb_decrypt(&single, &iv, buf))?,
+           megabytes_per_second(|buf| cfb_decrypt(&aes, &iv, buf))?);
+    report("ofb encrypt",
+           megabytes_per_second(|buf| ofb_encrypt(&single, &iv, buf))?,
+           megabytes_per_second(|buf| ofb_encrypt(&aes, &iv, buf))?);
+    report("ofb decrypt",
+           megabytes_per_second(|buf| ofb_decrypt(&single, &iv, buf))?,
+           megabytes_per_second(|buf| ofb_decrypt(&aes, &iv, buf))?);
+    report("ctr",
+           megabytes_per_second(|buf| ctr_encrypt(&single, &layout, buf))?,
+           megabytes_per_second(|buf| ctr_encrypt(&aes, &layout, buf))?);
+
+    Ok(())
+}
\ No newline at end of file
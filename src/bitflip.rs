@@ -0,0 +1,254 @@
+use crate::Error;
+use crate::aes::{Aes128, encrypt_cbc, decrypt_cbc};
+use crate::modes::{CounterLayout, ctr_encrypt, ctr_decrypt};
+use crate::padding::Padding;
+use crate::byte_at_a_time::detect_prefix_len;
+use crate::random::random_key;
+use std::str::from_utf8;
+
+const PREFIX: &str = "comment1=cooking%20MCs;userdata=";
+const SUFFIX: &str = ";comment2=%20like%20a%20pound%20of%20bacon";
+
+/// quotes out the characters that would let user data add its own pairs
+fn quote(input: &str) -> String {
+    input.replace(';', "%3b").replace('=', "%3d")
+}
+
+/// true if the bytes hold ;admin=true; anywhere, the rest of the plain text
+/// doesn't have to make sense
+pub fn contains_admin(plain_text: &[u8]) -> bool {
+    plain_text.windows(12).any(|w| w == b";admin=true;")
+}
+
+/// The challenge 16 server, user data wrapped in comments and CBC encrypted
+/// under a key and iv that stay the same for its lifetime.
+pub struct CbcUserData {
+    cipher: Aes128,
+    iv: [u8; 16],
+}
+
+impl CbcUserData {
+    pub fn new() -> CbcUserData {
+        CbcUserData { cipher: Aes128::new(&random_key()), iv: random_key() }
+    }
+
+    pub fn encrypt(&self, user_data: &str) -> Result<Vec<u8>, Error> {
+        let plain_text = format!("{}{}{}", PREFIX, quote(user_data), SUFFIX);
+
+        encrypt_cbc(&self.cipher, plain_text.as_bytes(), &self.iv, Padding::Pkcs7)
+    }
+
+    pub fn is_admin(&self, cipher_text: &[u8]) -> Result<bool, Error> {
+        Ok(contains_admin(&decrypt_cbc(&self.cipher, cipher_text, &self.iv, Padding::Pkcs7)?))
+    }
+}
+
+impl Default for CbcUserData {
+    fn default() -> CbcUserData {
+        CbcUserData::new()
+    }
+}
+
+/// The challenge 26 server, the same as CbcUserData but in CTR mode.
+pub struct CtrUserData {
+    cipher: Aes128,
+    layout: CounterLayout,
+}
+
+impl CtrUserData {
+    pub fn new() -> CtrUserData {
+        CtrUserData { cipher: Aes128::new(&random_key()), layout: CounterLayout::LittleEndian64(0) }
+    }
+
+    pub fn encrypt(&self, user_data: &str) -> Result<Vec<u8>, Error> {
+        let mut buf = format!("{}{}{}", PREFIX, quote(user_data), SUFFIX).into_bytes();
+        ctr_encrypt(&self.cipher, &self.layout, &mut buf)?;
+
+        Ok(buf)
+    }
+
+    pub fn is_admin(&self, cipher_text: &[u8]) -> Result<bool, Error> {
+        let mut buf = cipher_text.to_vec();
+        ctr_decrypt(&self.cipher, &self.layout, &mut buf)?;
+
+        Ok(contains_admin(&buf))
+    }
+}
+
+impl Default for CtrUserData {
+    fn default() -> CtrUserData {
+        CtrUserData::new()
+    }
+}
+
+/// Changes the CBC plain text at offset from known to wanted by flipping the
+/// same bits in the cipher text block before it, which garbles that block.
+/// The first block can be reached by passing the iv in front of the cipher
+/// text, and the change has to stay within a single block.
+pub fn cbc_bitflip(cipher_text: &mut [u8], offset: usize, known: &[u8], wanted: &[u8]) -> Result<(), Error> {
+    let block_size = 16;
+
+    if known.len() != wanted.len() {
+        return Err(Error::Generic("known and wanted text differ in length"));
+    }
+    if offset < block_size {
+        return Err(Error::Generic("the first block can't be changed without the iv"));
+    }
+    if offset + wanted.len() > cipher_text.len() {
+        return Err(Error::Generic("offset is past the end of the cipher text"));
+    }
+    if !wanted.is_empty() && offset / block_size != (offset + wanted.len() - 1) / block_size {
+        return Err(Error::Generic("wanted text has to stay within one block"));
+    }
+
+    let previous = &mut cipher_text[offset - block_size..offset - block_size + wanted.len()];
+    for ((c, k), w) in previous.iter_mut().zip(known).zip(wanted) {
+        *c ^= k ^ w;
+    }
+
+    Ok(())
+}
+
+/// Gets ;admin=true; into the plain text without sending ; or =, a block
+/// of filler is sent first to be garbled by the flip.
+pub fn inject_admin<F>(mut encrypt: F) -> Result<Vec<u8>, Error>
+    where F: FnMut(&str) -> Result<Vec<u8>, Error> {
+    let block_size = 16;
+    let mut oracle = |input: &[u8]| encrypt(from_utf8(input)?);
+
+    let prefix_len = detect_prefix_len(&mut oracle, block_size)?;
+    let align = (block_size - prefix_len % block_size) % block_size;
+
+    let known = b":admin<true:";
+    let mut user_data = vec![b'A'; align + block_size];
+    user_data.extend(known);
+
+    let mut cipher_text = oracle(&user_data)?;
+    cbc_bitflip(&mut cipher_text, prefix_len + align + block_size, known, b";admin=true;")?;
+
+    Ok(cipher_text)
+}
+
+/// Changes the CTR plain text at offset from known to wanted, every bit
+/// flipped in the cipher text flips the same bit in the plain text and
+/// nothing else.
+pub fn ctr_bitflip(cipher_text: &mut [u8], offset: usize, known: &[u8], wanted: &[u8]) -> Result<(), Error> {
+    if known.len() != wanted.len() {
+        return Err(Error::Generic("known and wanted text differ in length"));
+    }
+    if offset + wanted.len() > cipher_text.len() {
+        return Err(Error::Generic("offset is past the end of the cipher text"));
+    }
+
+    for ((c, k), w) in cipher_text[offset..].iter_mut().zip(known).zip(wanted) {
+        *c ^= k ^ w;
+    }
+
+    Ok(())
+}
+
+/// The CTR version of inject_admin, there is no block to sacrifice and the
+/// prefix is found a byte at a time.
+pub fn inject_admin_ctr<F>(mut encrypt: F) -> Result<Vec<u8>, Error>
+    where F: FnMut(&str) -> Result<Vec<u8>, Error> {
+    let mut oracle = |input: &[u8]| encrypt(from_utf8(input)?);
+
+    let prefix_len = detect_prefix_len(&mut oracle, 1)?;
+
+    let known = b":admin<true:";
+    let mut cipher_text = oracle(known)?;
+    ctr_bitflip(&mut cipher_text, prefix_len, known, b";admin=true;")?;
+
+    Ok(cipher_text)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aes::{encrypt_aes_cbc, decrypt_aes_cbc};
+    use crate::bitflip::{CbcUserData, CtrUserData, cbc_bitflip, ctr_bitflip, contains_admin, inject_admin, inject_admin_ctr};
+
+    #[test]
+    fn user_data_is_quoted() {
+        let server = CbcUserData::new();
+
+        assert!(!server.is_admin(&server.encrypt(";admin=true;").unwrap()).unwrap());
+        assert!(!server.is_admin(&server.encrypt("x;admin=true;x").unwrap()).unwrap());
+    }
+
+    #[test]
+    fn bitflip_injects_admin() {
+        let server = CbcUserData::new();
+
+        let cipher_text = inject_admin(|user_data| server.encrypt(user_data)).unwrap();
+
+        assert!(server.is_admin(&cipher_text).unwrap());
+    }
+
+    #[test]
+    fn bitflip_at_any_offset() {
+        let key = [4; 16];
+        let iv = [5; 16];
+        let plain_text = vec![b'.'; 64];
+
+        for offset in 16..60 {
+            let wanted = &b"hello"[..(16 - offset % 16).min(5)];
+            let mut cipher_text = encrypt_aes_cbc(&plain_text, &key, &iv).unwrap();
+
+            cbc_bitflip(&mut cipher_text, offset, &plain_text[..wanted.len()], wanted).unwrap();
+
+            let result = decrypt_aes_cbc(&cipher_text, &key, &iv).unwrap();
+            assert_eq!(wanted, &result[offset..offset + wanted.len()]);
+        }
+    }
+
+    #[test]
+    fn bitflip_first_block_through_iv() {
+        let key = [4; 16];
+        let plain_text = b"role=user;      ";
+        let mut iv_and_cipher_text = vec![5; 16];
+        iv_and_cipher_text.extend(encrypt_aes_cbc(plain_text, &key, &[5; 16]).unwrap());
+
+        cbc_bitflip(&mut iv_and_cipher_text, 16, b"role=user;  ", b";admin=true;").unwrap();
+
+        let (iv, cipher_text) = iv_and_cipher_text.split_at(16);
+        let mut fixed_iv = [0; 16];
+        fixed_iv.copy_from_slice(iv);
+        assert!(contains_admin(&decrypt_aes_cbc(cipher_text, &key, &fixed_iv).unwrap()));
+    }
+
+    #[test]
+    fn bitflip_bad_arguments() {
+        let mut cipher_text = vec![0; 32];
+
+        assert!(cbc_bitflip(&mut cipher_text, 8, b"a", b"b").is_err());
+        assert!(cbc_bitflip(&mut cipher_text, 30, b"abc", b"def").is_err());
+        assert!(cbc_bitflip(&mut cipher_text, 20, b"ab", b"def").is_err());
+        assert!(cbc_bitflip(&mut cipher_text, 14 + 16, b"ab", b"de").is_ok());
+    }
+
+    #[test]
+    fn ctr_user_data_is_quoted() {
+        let server = CtrUserData::new();
+
+        assert!(!server.is_admin(&server.encrypt(";admin=true;").unwrap()).unwrap());
+    }
+
+    #[test]
+    fn ctr_bitflip_injects_admin() {
+        let server = CtrUserData::new();
+
+        let cipher_text = inject_admin_ctr(|user_data| server.encrypt(user_data)).unwrap();
+
+        assert!(server.is_admin(&cipher_text).unwrap());
+    }
+
+    #[test]
+    fn ctr_bitflip_bad_arguments() {
+        let mut cipher_text = vec![0; 8];
+
+        assert!(ctr_bitflip(&mut cipher_text, 6, b"abc", b"def").is_err());
+        assert!(ctr_bitflip(&mut cipher_text, 0, b"ab", b"def").is_err());
+        assert!(ctr_bitflip(&mut cipher_text, 5, b"abc", b"def").is_ok());
+        assert_eq!(vec![0, 0, 0, 0, 0, 5, 7, 5], cipher_text);
+    }
+}
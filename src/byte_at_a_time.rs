@@ -0,0 +1,188 @@
+use crate::Error;
+use crate::aes::{Aes128, encrypt_ecb};
+use crate::padding::Padding;
+use crate::random::{random_bytes, random_key, random_range};
+
+/// The challenge 12 and 14 server, ECB under a hidden key with a secret
+/// suffix after the attacker's input, and for 14 a random prefix before it.
+pub struct EcbOracle {
+    cipher: Aes128,
+    prefix: Vec<u8>,
+    suffix: Vec<u8>,
+}
+
+impl EcbOracle {
+    pub fn new(suffix: &[u8]) -> EcbOracle {
+        EcbOracle { cipher: Aes128::new(&random_key()), prefix: vec![], suffix: suffix.to_vec() }
+    }
+
+    /// the prefix is picked once, between 0 and 63 random bytes
+    pub fn with_random_prefix(suffix: &[u8]) -> EcbOracle {
+        let prefix = random_bytes(random_range(0, 64));
+        EcbOracle { cipher: Aes128::new(&random_key()), prefix, suffix: suffix.to_vec() }
+    }
+
+    pub fn encrypt(&self, input: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut plain_text = self.prefix.clone();
+        plain_text.extend(input);
+        plain_text.extend(&self.suffix);
+
+        encrypt_ecb(&self.cipher, &plain_text, Padding::Pkcs7)
+    }
+}
+
+/// first index i where block i and block i + 1 are the same
+fn first_repeated_block(cipher_text: &[u8], block_size: usize) -> Option<usize> {
+    let blocks: Vec<&[u8]> = cipher_text.chunks_exact(block_size).collect();
+    blocks.windows(2).position(|w| w[0] == w[1])
+}
+
+/// Feeds the oracle longer and longer input until the output grows, for a
+/// padded block cipher it grows by exactly one block. Returns the block size
+/// and how many bytes it took to make it grow.
+pub fn first_length_jump<F>(oracle: &mut F) -> Result<(usize, usize), Error>
+    where F: FnMut(&[u8]) -> Result<Vec<u8>, Error> {
+    let start = oracle(&[])?.len();
+
+    for i in 1..=256 {
+        let len = oracle(&vec![b'A'; i])?.len();
+        if len > start {
+            return Ok((len - start, i));
+        }
+    }
+
+    Err(Error::Generic("output never grew, not a block cipher"))
+}
+
+pub fn detect_block_size<F>(oracle: &mut F) -> Result<usize, Error>
+    where F: FnMut(&[u8]) -> Result<Vec<u8>, Error> {
+    Ok(first_length_jump(oracle)?.0)
+}
+
+/// Three blocks of the same byte always contain two whole aligned blocks,
+/// which only encrypt to the same thing under ECB.
+pub fn detect_ecb<F>(oracle: &mut F, block_size: usize) -> Result<bool, Error>
+    where F: FnMut(&[u8]) -> Result<Vec<u8>, Error> {
+    let cipher_text = oracle(&vec![b'A'; block_size * 3])?;
+
+    Ok(first_repeated_block(&cipher_text, block_size).is_some())
+}
+
+/// index of the first block where the two cipher texts differ
+fn first_changed_block(a: &[u8], b: &[u8], block_size: usize) -> Option<usize> {
+    a.chunks(block_size).zip(b.chunks(block_size)).position(|(a, b)| a != b)
+}
+
+/// Changing the first input byte shows which block the input starts in, and
+/// that block stops changing with the last input byte once the input fills
+/// it out. Doesn't depend on what the prefix or suffix contain.
+pub fn detect_prefix_len<F>(oracle: &mut F, block_size: usize) -> Result<usize, Error>
+    where F: FnMut(&[u8]) -> Result<Vec<u8>, Error> {
+    let start_block = first_changed_block(&oracle(b"A")?, &oracle(b"B")?, block_size)
+        .ok_or(Error::Generic("the output doesn't depend on the input"))?;
+
+    for filled in 0..=block_size {
+        let mut a = vec![b'A'; filled + 1];
+        let mut b = a.clone();
+        a[filled] = b'X';
+        b[filled] = b'Y';
+
+        if first_changed_block(&oracle(&a)?, &oracle(&b)?, block_size) != Some(start_block) {
+            return Ok((start_block + 1) * block_size - filled);
+        }
+    }
+
+    Err(Error::Generic("input never filled out a block, not ECB"))
+}
+
+/// With n bytes of input making the output grow, prefix, n bytes and the
+/// suffix exactly fill the output before the jump.
+pub fn detect_suffix_len<F>(oracle: &mut F, prefix_len: usize) -> Result<usize, Error>
+    where F: FnMut(&[u8]) -> Result<Vec<u8>, Error> {
+    let start = oracle(&[])?.len();
+    let (_, input_len) = first_length_jump(oracle)?;
+
+    (start - input_len).checked_sub(prefix_len).ok_or(Error::Generic("prefix is longer than the output"))
+}
+
+/// Recovers the secret that an ECB oracle appends to its input, one byte at
+/// a time. Works with or without a prefix in front of the input.
+pub fn recover_suffix<F>(mut oracle: F) -> Result<Vec<u8>, Error>
+    where F: FnMut(&[u8]) -> Result<Vec<u8>, Error> {
+    let block_size = detect_block_size(&mut oracle)?;
+    if !detect_ecb(&mut oracle, block_size)? {
+        return Err(Error::Generic("oracle isn't ECB"));
+    }
+    let prefix_len = detect_prefix_len(&mut oracle, block_size)?;
+    let suffix_len = detect_suffix_len(&mut oracle, prefix_len)?;
+
+    // input that lines our own bytes up with a block boundary
+    let align = (block_size - prefix_len % block_size) % block_size;
+    let skip = prefix_len + align;
+
+    let mut known = vec![b'A'; block_size - 1];
+    for n in 0..suffix_len {
+        let pad = vec![b'A'; align + block_size - 1 - n % block_size];
+        let cipher_text = oracle(&pad)?;
+        let start = skip + (n / block_size) * block_size;
+        let target = &cipher_text[start..start + block_size];
+
+        // every candidate block in a single query, one block per byte value
+        let window = &known[known.len() - (block_size - 1)..];
+        let mut dictionary = vec![b'A'; align];
+        for guess in 0..=255u8 {
+            dictionary.extend(window);
+            dictionary.push(guess);
+        }
+        let cipher_text = oracle(&dictionary)?;
+
+        let found = cipher_text[skip..skip + 256 * block_size].chunks_exact(block_size).position(|b| b == target);
+        match found {
+            Some(byte) => known.push(byte as u8),
+            None => return Err(Error::Generic("no byte matched, the oracle isn't deterministic")),
+        }
+    }
+
+    Ok(known.split_off(block_size - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aes::Aes128;
+    use crate::byte_at_a_time::{EcbOracle, detect_block_size, detect_ecb, detect_prefix_len, detect_suffix_len, recover_suffix};
+
+    #[test]
+    fn detects_layout() {
+        for fill in [b'A', b'B', b's'].iter() {
+            for prefix_len in 0..40 {
+                let oracle = EcbOracle { cipher: Aes128::new(&[3; 16]), prefix: vec![*fill; prefix_len], suffix: b"AAAAsecret".to_vec() };
+                let mut query = |input: &[u8]| oracle.encrypt(input);
+
+                assert_eq!(16, detect_block_size(&mut query).unwrap());
+                assert!(detect_ecb(&mut query, 16).unwrap());
+                assert_eq!(prefix_len, detect_prefix_len(&mut query, 16).unwrap());
+                assert_eq!(10, detect_suffix_len(&mut query, prefix_len).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn recovers_suffix_of_every_length() {
+        for len in 0..40 {
+            let secret: Vec<u8> = (0..len as u8).map(|b| b.wrapping_mul(91)).collect();
+
+            let oracle = EcbOracle::new(&secret);
+            assert_eq!(secret, recover_suffix(|input| oracle.encrypt(input)).unwrap());
+
+            let oracle = EcbOracle::with_random_prefix(&secret);
+            assert_eq!(secret, recover_suffix(|input| oracle.encrypt(input)).unwrap());
+        }
+    }
+
+    #[test]
+    fn refuses_cbc() {
+        let oracle = |input: &[u8]| crate::aes::encrypt_aes_cbc(input, &[1; 16], &[2; 16]);
+
+        assert!(recover_suffix(oracle).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+use crate::Error;
+use crate::base64::from_base64;
+use crate::file::{file_to_vec, file_to_buf};
+use crate::hex::{parse_hex, to_hex};
+use std::io::{BufRead, Write};
+use std::str::from_utf8;
+
+// Crib dragging by hand for cipher texts that share a key stream. Placing a
+// guessed piece of plain text on one line fixes the key stream under it,
+// and with that the same columns of every other line.
+
+const HELP: &str = "\
+c <line> <pos> <text>  place text on a line at a position
+x <pos> <len>          forget the key stream for len bytes from pos
+s <file>               save the key stream
+l <file>               load a key stream
+q                      quit";
+
+/// Reads one cipher text per line. The file is taken as hex if every line
+/// is, and as base64 otherwise, so a base64 line that happens to look like
+/// hex doesn't get misread.
+pub fn load_cipher_texts(path: &str) -> Result<Vec<Vec<u8>>, Error> {
+    let lines = file_to_vec(path)?.into_iter().filter(|l| !l.is_empty()).collect::<Vec<String>>();
+    let hex = lines.iter().all(|l| l.len() % 2 == 0 && l.bytes().all(|c| c.is_ascii_hexdigit()));
+
+    lines.iter().map(|l| if hex { parse_hex(l) } else { from_base64(l) }).collect()
+}
+
+/// Key stream bytes are written as hex and unknown ones as __, so a saved
+/// key stream can be read and edited by hand.
+pub fn key_stream_to_string(key_stream: &[Option<u8>]) -> String {
+    key_stream.iter().map(|k| k.map_or("__".to_owned(), |k| to_hex(&[k]))).collect()
+}
+
+pub fn parse_key_stream(input: &str) -> Result<Vec<Option<u8>>, Error> {
+    let input = input.trim();
+    if input.len() % 2 != 0 {
+        return Err(Error::Generic("key stream has an odd number of digits"));
+    }
+
+    input.as_bytes().chunks(2).map(|pair| {
+        match pair {
+            b"__" => Ok(None),
+            _ => Ok(Some(parse_hex(from_utf8(pair)?)?[0])),
+        }
+    }).collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Crib { line: usize, position: usize, text: Vec<u8> },
+    Clear { position: usize, len: usize },
+    Save(String),
+    Load(String),
+    Quit,
+}
+
+fn parse_number(word: Option<&str>) -> Result<usize, Error> {
+    word.ok_or(Error::Generic("missing number"))?
+        .parse()
+        .map_err(|_| Error::Generic("not a number"))
+}
+
+/// The crib is everything after the position, spaces included.
+pub fn parse_command(input: &str) -> Result<Command, Error> {
+    let input = input.trim_end_matches(&['\r', '\n'][..]);
+    let mut words = input.splitn(4, ' ');
+
+    match words.next() {
+        Some("c") => {
+            let line = parse_number(words.next())?;
+            let position = parse_number(words.next())?;
+            let text = words.next().ok_or(Error::Generic("missing crib"))?;
+            Ok(Command::Crib { line, position, text: text.as_bytes().to_vec() })
+        },
+        Some("x") => Ok(Command::Clear { position: parse_number(words.next())?, len: parse_number(words.next())? }),
+        Some("s") => Ok(Command::Save(words.next().ok_or(Error::Generic("missing file name"))?.to_owned())),
+        Some("l") => Ok(Command::Load(words.next().ok_or(Error::Generic("missing file name"))?.to_owned())),
+        Some("q") => Ok(Command::Quit),
+        _ => Err(Error::Generic("unknown command")),
+    }
+}
+
+pub struct CribSession {
+    cipher_texts: Vec<Vec<u8>>,
+    key_stream: Vec<Option<u8>>,
+}
+
+impl CribSession {
+    pub fn new(cipher_texts: Vec<Vec<u8>>) -> CribSession {
+        let len = cipher_texts.iter().map(|c| c.len()).max().unwrap_or(0);
+
+        CribSession { cipher_texts, key_stream: vec![None; len] }
+    }
+
+    #[cfg(test)]
+    pub fn key_stream(&self) -> &[Option<u8>] {
+        &self.key_stream
+    }
+
+    /// Sets the key stream so that line reads text from position on.
+    pub fn place_crib(&mut self, line: usize, position: usize, text: &[u8]) -> Result<(), Error> {
+        let cipher_text = self.cipher_texts.get(line).ok_or(Error::Generic("no such line"))?;
+        let end = position.checked_add(text.len()).ok_or(Error::Generic("crib goes past the end of the line"))?;
+        if end > cipher_text.len() {
+            return Err(Error::Generic("crib goes past the end of the line"));
+        }
+
+        for (i, t) in text.iter().enumerate() {
+            self.key_stream[position + i] = Some(cipher_text[position + i] ^ t);
+        }
+
+        Ok(())
+    }
+
+    /// Forgets len bytes of key stream from position on. The part of the
+    /// range past the end of the lines is ignored, but a range whose end
+    /// doesn't fit in a usize is an error.
+    pub fn clear(&mut self, position: usize, len: usize) -> Result<(), Error> {
+        let end = position.checked_add(len).ok_or(Error::Generic("range to clear is too long"))?;
+        for k in self.key_stream.iter_mut().take(end).skip(position) {
+            *k = None;
+        }
+
+        Ok(())
+    }
+
+    /// A loaded key stream longer than the cipher texts is cut short, and a
+    /// shorter one leaves the rest unknown.
+    pub fn set_key_stream(&mut self, key_stream: &[Option<u8>]) {
+        for (k, l) in self.key_stream.iter_mut().zip(key_stream.iter().chain(std::iter::repeat(&None))) {
+            *k = *l;
+        }
+    }
+
+    /// The plain texts as far as the key stream is known, _ where it isn't
+    /// and ? where the guess gives something unprintable.
+    pub fn plain_texts(&self) -> Vec<String> {
+        self.cipher_texts.iter().map(|cipher_text| {
+            cipher_text.iter().zip(self.key_stream.iter()).map(|(c, k)| {
+                match k.map(|k| c ^ k) {
+                    None => '_',
+                    Some(p) if p == b' ' || p.is_ascii_graphic() => p as char,
+                    Some(_) => '?',
+                }
+            }).collect()
+        }).collect()
+    }
+
+    /// A ruler of positions, the key stream under it as two rows of hex
+    /// digits with the high one on top and _ where it isn't known, then the
+    /// plain texts one per line.
+    pub fn render(&self) -> String {
+        let mut ruler = String::new();
+        for i in (0..self.key_stream.len()).step_by(10) {
+            ruler.push_str(&format!("{:<10}", i));
+        }
+        let digits = |shift: u8| self.key_stream.iter()
+            .map(|k| k.map_or('_', |k| char::from_digit(((k >> shift) & 0xf) as u32, 16).unwrap()))
+            .collect::<String>();
+
+        let mut out = format!("     {}\n", ruler.trim_end());
+        out.push_str(&format!("key  {}\n     {}\n", digits(4), digits(0)));
+        for (i, plain_text) in self.plain_texts().iter().enumerate() {
+            out.push_str(&format!("{:>3}  {}\n", i, plain_text));
+        }
+        out
+    }
+
+    fn execute(&mut self, command: Command) -> Result<(), Error> {
+        match command {
+            Command::Crib { line, position, text } => self.place_crib(line, position, &text)?,
+            Command::Clear { position, len } => self.clear(position, len)?,
+            Command::Save(path) => std::fs::write(path, key_stream_to_string(&self.key_stream))?,
+            Command::Load(path) => self.set_key_stream(&parse_key_stream(from_utf8(&file_to_buf(&path)?)?)?),
+            Command::Quit => {},
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the crib dragging prompt on stdin for the cipher texts in path.
+pub fn run(path: &str) -> Result<(), Error> {
+    let mut session = CribSession::new(load_cipher_texts(path)?);
+    let stdin = std::io::stdin();
+
+    println!("{}\n", HELP);
+    loop {
+        print!("{}> ", session.render());
+        std::io::stdout().flush()?;
+
+        let mut input = String::new();
+        if stdin.lock().read_line(&mut input)? == 0 {
+            return Ok(());
+        }
+
+        match parse_command(&input) {
+            Ok(Command::Quit) => return Ok(()),
+            Ok(command) => {
+                if let Err(e) = session.execute(command) {
+                    println!("{:?}", e);
+                }
+            },
+            Err(e) => println!("{:?}\n{}", e, HELP),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crib::{CribSession, Command, load_cipher_texts, parse_command, key_stream_to_string, parse_key_stream};
+    use crate::xor::xor;
+
+    fn two_lines() -> CribSession {
+        let key_stream = b"0123456789abcdef";
+        CribSession::new(vec![
+            xor(b"the cat sat", &key_stream[..11]).unwrap(),
+            xor(b"a dog ran off", &key_stream[..13]).unwrap(),
+        ])
+    }
+
+    #[test]
+    fn crib_shows_other_lines() {
+        let mut session = two_lines();
+        assert_eq!(vec!["___________", "_____________"], session.plain_texts());
+
+        session.place_crib(0, 4, b"cat").unwrap();
+
+        assert_eq!(vec!["____cat____", "____g r______"], session.plain_texts());
+    }
+
+    #[test]
+    fn crib_past_the_end() {
+        let mut session = two_lines();
+
+        assert!(session.place_crib(0, 9, b"sat").is_err());
+        assert!(session.place_crib(2, 0, b"a").is_err());
+        assert!(session.place_crib(1, 10, b"off").is_ok());
+    }
+
+    #[test]
+    fn render_shows_key_stream() {
+        let mut session = two_lines();
+        session.place_crib(0, 2, b"e c").unwrap();
+
+        let expected = vec!["     0         10", "key  __333________", "     __234________", "  0  __e c______", "  1  __dog________"];
+        assert_eq!(expected, session.render().lines().collect::<Vec<&str>>());
+    }
+
+    #[test]
+    fn clear_forgets() {
+        let mut session = two_lines();
+        session.place_crib(1, 0, b"a dog").unwrap();
+        session.clear(1, 2).unwrap();
+
+        assert_eq!("t__ c______", session.plain_texts()[0]);
+    }
+
+    #[test]
+    fn huge_positions() {
+        let mut session = two_lines();
+        session.place_crib(0, 0, b"the").unwrap();
+
+        assert!(session.place_crib(0, usize::MAX, b"sat").is_err());
+        assert!(session.clear(usize::MAX, 2).is_err());
+        assert!(session.clear(2, usize::MAX).is_err());
+        assert!(session.clear(usize::MAX, 0).is_ok());
+        assert_eq!("the________", session.plain_texts()[0]);
+    }
+
+    #[test]
+    fn key_stream_roundtrip() {
+        let mut session = two_lines();
+        session.place_crib(0, 2, b"e c").unwrap();
+
+        let saved = key_stream_to_string(session.key_stream());
+        assert_eq!("____323334________________", saved);
+
+        let mut loaded = two_lines();
+        loaded.set_key_stream(&parse_key_stream(&saved).unwrap());
+        assert_eq!(session.plain_texts(), loaded.plain_texts());
+    }
+
+    #[test]
+    fn loads_base64() {
+        let cipher_texts = load_cipher_texts("res/19.txt").unwrap();
+
+        assert_eq!(40, cipher_texts.len());
+        assert_eq!(b"I have met them at close of day".to_vec(), cipher_texts[0]);
+    }
+
+    #[test]
+    fn bad_key_stream() {
+        assert!(parse_key_stream("12_").is_err());
+        assert!(parse_key_stream("zz").is_err());
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(Command::Crib { line: 2, position: 5, text: b"the end ".to_vec() }, parse_command("c 2 5 the end \n").unwrap());
+        assert_eq!(Command::Clear { position: 3, len: 4 }, parse_command("x 3 4").unwrap());
+        assert_eq!(Command::Save("ks.txt".to_owned()), parse_command("s ks.txt").unwrap());
+        assert_eq!(Command::Quit, parse_command("q").unwrap());
+        assert!(parse_command("c 1").is_err());
+        assert!(parse_command("x a 4").is_err());
+        assert!(parse_command("what").is_err());
+    }
+}
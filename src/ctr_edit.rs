@@ -0,0 +1,71 @@
+use crate::Error;
+use crate::aes::{encrypt_aes_ctr, edit_aes_ctr};
+use crate::modes::CounterLayout;
+use crate::random::random_key;
+use crate::xor::xor;
+
+/// The challenge 25 setup, a CTR cipher text under a key the attacker never
+/// sees, with the edit function exposed as an api call.
+pub struct EditOracle {
+    key: [u8; 16],
+    layout: CounterLayout,
+    cipher_text: Vec<u8>,
+}
+
+impl EditOracle {
+    pub fn new(plain_text: &[u8]) -> Result<EditOracle, Error> {
+        let key = random_key();
+        let layout = CounterLayout::LittleEndian64(0);
+        let cipher_text = encrypt_aes_ctr(plain_text, &key, &layout)?;
+
+        Ok(EditOracle { key, layout, cipher_text })
+    }
+
+    pub fn cipher_text(&self) -> &[u8] {
+        &self.cipher_text
+    }
+
+    /// re-encrypts new_text at offset under the hidden key and hands back the
+    /// resulting cipher text, the stored one is left alone
+    pub fn edit(&self, offset: usize, new_text: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut cipher_text = self.cipher_text.clone();
+        edit_aes_ctr(&mut cipher_text, &self.key, &self.layout, offset, new_text)?;
+        Ok(cipher_text)
+    }
+}
+
+/// Recovers the plain text behind a CTR cipher text from an edit call, writing
+/// zeros over everything makes the edit hand back the raw key stream.
+pub fn recover_plain_text<F>(cipher_text: &[u8], mut edit: F) -> Result<Vec<u8>, Error>
+    where F: FnMut(usize, &[u8]) -> Result<Vec<u8>, Error> {
+    let key_stream = edit(0, &vec![0; cipher_text.len()])?;
+    if key_stream.len() < cipher_text.len() {
+        return Err(Error::Generic("edit gave back less than the cipher text"));
+    }
+
+    xor(cipher_text, &key_stream[..cipher_text.len()])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ctr_edit::{EditOracle, recover_plain_text};
+
+    #[test]
+    fn recover_through_edit() {
+        let plain_text = b"attack at dawn, retreat at dusk, and bring snacks".to_vec();
+        let oracle = EditOracle::new(&plain_text).unwrap();
+
+        assert_ne!(plain_text, oracle.cipher_text().to_vec());
+
+        let result = recover_plain_text(oracle.cipher_text(), |offset, new_text| oracle.edit(offset, new_text)).unwrap();
+
+        assert_eq!(plain_text, result);
+    }
+
+    #[test]
+    fn short_edit_result() {
+        let result = recover_plain_text(&[1, 2, 3], |_, _| Ok(vec![0; 2]));
+
+        assert!(result.is_err());
+    }
+}
\ No newline at end of file
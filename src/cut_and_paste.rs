@@ -0,0 +1,98 @@
+use crate::Error;
+use crate::aes::{Aes128, encrypt_ecb, decrypt_ecb};
+use crate::byte_at_a_time::{detect_prefix_len, detect_suffix_len};
+use crate::kv::{encode_kv, parse_kv};
+use crate::padding::{Padding, add_padding};
+use crate::random::random_key;
+use std::str::from_utf8;
+
+pub fn profile_for(email: &str) -> String {
+    encode_kv(&[("email", email), ("uid", "10"), ("role", "user")])
+}
+
+/// The challenge 13 server, hands out encrypted profiles and reads them back.
+pub struct ProfileOracle {
+    cipher: Aes128,
+}
+
+impl ProfileOracle {
+    pub fn new() -> ProfileOracle {
+        ProfileOracle { cipher: Aes128::new(&random_key()) }
+    }
+
+    pub fn encrypt_profile(&self, email: &str) -> Result<Vec<u8>, Error> {
+        encrypt_ecb(&self.cipher, profile_for(email).as_bytes(), Padding::Pkcs7)
+    }
+
+    pub fn decrypt_profile(&self, cipher_text: &[u8]) -> Result<Vec<(String, String)>, Error> {
+        parse_kv(from_utf8(&decrypt_ecb(&self.cipher, cipher_text, Padding::Pkcs7)?)?)
+    }
+}
+
+impl Default for ProfileOracle {
+    fn default() -> ProfileOracle {
+        ProfileOracle::new()
+    }
+}
+
+/// Builds a profile cipher text that ends in role=admin out of blocks the
+/// oracle encrypted, without ever sending it & or =. Relies on the role
+/// being the last value and it being "user".
+pub fn make_admin<F>(mut encrypt_profile: F) -> Result<Vec<u8>, Error>
+    where F: FnMut(&str) -> Result<Vec<u8>, Error> {
+    let block_size = 16;
+    let mut oracle = |input: &[u8]| encrypt_profile(from_utf8(input)?);
+
+    let prefix_len = detect_prefix_len(&mut oracle, block_size)?;
+    let suffix_len = detect_suffix_len(&mut oracle, prefix_len)?;
+    let align = (block_size - prefix_len % block_size) % block_size;
+    let skip = prefix_len + align;
+
+    // a block that is "admin" and its padding, as if it ended a message
+    let mut admin = b"admin".to_vec();
+    add_padding(&mut admin, block_size)?;
+    let mut email = vec![b'A'; align];
+    email.extend(&admin);
+    let admin_block = oracle(&email)?[skip..skip + block_size].to_vec();
+
+    // an email long enough to push the old role value into a block of its own
+    let head_len = prefix_len + suffix_len - "user".len();
+    let email_len = (block_size - head_len % block_size) % block_size;
+    let mut cipher_text = oracle(&vec![b'A'; email_len])?;
+    cipher_text.truncate(head_len + email_len);
+    cipher_text.extend(admin_block);
+
+    Ok(cipher_text)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cut_and_paste::{ProfileOracle, profile_for, make_admin};
+
+    #[test]
+    fn profile_for_escapes() {
+        assert_eq!("email=foo@bar.com&uid=10&role=user", profile_for("foo@bar.com"));
+        assert_eq!("email=foo@bar.com%26role%3dadmin&uid=10&role=user", profile_for("foo@bar.com&role=admin"));
+    }
+
+    #[test]
+    fn profile_roundtrip() {
+        let oracle = ProfileOracle::new();
+        let profile = oracle.decrypt_profile(&oracle.encrypt_profile("foo@bar.com&role=admin").unwrap()).unwrap();
+
+        assert_eq!(("email".to_owned(), "foo@bar.com&role=admin".to_owned()), profile[0]);
+        assert_eq!(("role".to_owned(), "user".to_owned()), profile[2]);
+    }
+
+    #[test]
+    fn cut_and_paste_admin() {
+        let oracle = ProfileOracle::new();
+
+        let cipher_text = make_admin(|email| oracle.encrypt_profile(email)).unwrap();
+        let profile = oracle.decrypt_profile(&cipher_text).unwrap();
+
+        assert_eq!(3, profile.len());
+        assert_eq!(("uid".to_owned(), "10".to_owned()), profile[1]);
+        assert_eq!(("role".to_owned(), "admin".to_owned()), profile[2]);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::Error;
+use crate::aes::encrypt_aes_ctr;
+use crate::chop_and_transpose;
+use crate::modes::CounterLayout;
+use crate::string::score_english;
+use crate::xor::{xor, guess_xor_byte_with_space_vec};
+
+/// Encrypts every plain text under the same key and nonce, like the servers
+/// in challenges 19 and 20, so they all share one key stream.
+pub fn encrypt_fixed_nonce(plain_texts: &[Vec<u8>], key: &[u8; 16]) -> Result<Vec<Vec<u8>>, Error> {
+    let layout = CounterLayout::LittleEndian64(0);
+
+    plain_texts.iter().map(|p| encrypt_aes_ctr(p, key, &layout)).collect()
+}
+
+/// Tries every byte on a column of key stream and keeps the guess unless
+/// some other byte gives something closer to english.
+fn refine_key_byte(column: &[u8], guess: u8) -> u8 {
+    let score = |k: u8| score_english(&column.iter().map(|c| c ^ k).collect::<Vec<u8>>());
+
+    let mut best = guess;
+    let mut best_score = score(guess);
+    for k in 0..=255 {
+        let s = score(k);
+        if s < best_score {
+            best = k;
+            best_score = s;
+        }
+    }
+
+    best
+}
+
+/// Recovers the key stream shared by the cipher texts, as far as the
+/// shortest one goes. Cut to the same length they are one repeating key
+/// xor, so each column is guessed as having the most spaces and then
+/// refined by scoring it against english.
+pub fn break_fixed_nonce(cipher_texts: &[Vec<u8>]) -> Result<Vec<u8>, Error> {
+    let len = cipher_texts.iter().map(|c| c.len()).min().ok_or(Error::Generic("no cipher texts"))?;
+    if len == 0 {
+        return Err(Error::Generic("empty cipher text"));
+    }
+
+    let joined = cipher_texts.iter().flat_map(|c| c[..len].to_vec()).collect::<Vec<u8>>();
+    let columns = chop_and_transpose(&joined, len);
+    let guesses = guess_xor_byte_with_space_vec(&columns);
+
+    Ok(columns.iter().zip(guesses).map(|(column, guess)| refine_key_byte(column, guess)).collect())
+}
+
+/// Decrypts as much of the cipher text as the key stream covers.
+pub fn apply_key_stream(cipher_text: &[u8], key_stream: &[u8]) -> Result<Vec<u8>, Error> {
+    let len = cipher_text.len().min(key_stream.len());
+
+    xor(&cipher_text[..len], &key_stream[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixed_nonce::{encrypt_fixed_nonce, break_fixed_nonce, apply_key_stream};
+    use crate::base64_lines;
+    use crate::random::random_key;
+
+    #[test]
+    fn needs_cipher_texts() {
+        assert!(break_fixed_nonce(&[]).is_err());
+        assert!(break_fixed_nonce(&[vec![1, 2], vec![]]).is_err());
+    }
+
+    #[test]
+    fn key_stream_covers_shortest() {
+        let mut plain_texts = base64_lines("res/19.txt").unwrap();
+        plain_texts.push(b"and a second".to_vec());
+        let cipher_texts = encrypt_fixed_nonce(&plain_texts, &random_key()).unwrap();
+
+        let key_stream = break_fixed_nonce(&cipher_texts).unwrap();
+
+        assert_eq!(12, key_stream.len());
+        for (plain_text, cipher_text) in plain_texts.iter().zip(cipher_texts.iter()) {
+            let result = apply_key_stream(cipher_text, &key_stream).unwrap();
+
+            assert!(plain_text[..12].eq_ignore_ascii_case(&result));
+        }
+    }
+}
@@ -48,6 +48,6 @@ mod tests {
 
     #[test]
     fn to_hex_test() {
-        assert_eq!("010203", to_hex(&vec![1, 2, 3]));
+        assert_eq!("010203", to_hex(&[1, 2, 3]));
     }
 }
\ No newline at end of file
@@ -0,0 +1,98 @@
+use crate::Error;
+use crate::aes::{Aes128, encrypt_cbc, decrypt_cbc};
+use crate::hex::{parse_hex, to_hex};
+use crate::padding::Padding;
+use crate::random::random_key;
+use crate::xor::xor;
+
+const ERROR_PREFIX: &str = "invalid ascii in ";
+
+/// The challenge 27 server, CBC with the key reused as the iv. Messages that
+/// aren't plain ascii are rejected with the plain text in the error.
+pub struct KeyAsIvServer {
+    key: [u8; 16],
+    cipher: Aes128,
+}
+
+impl KeyAsIvServer {
+    pub fn new() -> KeyAsIvServer {
+        let key = random_key();
+        KeyAsIvServer { key, cipher: Aes128::new(&key) }
+    }
+
+    pub fn encrypt(&self, message: &[u8]) -> Result<Vec<u8>, Error> {
+        encrypt_cbc(&self.cipher, message, &self.key, Padding::Pkcs7)
+    }
+
+    pub fn check(&self, cipher_text: &[u8]) -> Result<(), Error> {
+        let plain_text = decrypt_cbc(&self.cipher, cipher_text, &self.key, Padding::Pkcs7)?;
+        if !plain_text.is_ascii() {
+            return Err(Error::GenericStr(format!("{}{}", ERROR_PREFIX, to_hex(&plain_text))));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for KeyAsIvServer {
+    fn default() -> KeyAsIvServer {
+        KeyAsIvServer::new()
+    }
+}
+
+/// Sends C0, zeros, C0 followed by the rest of a cipher text of at least
+/// five blocks, so the padding at the end still checks out. The first plain
+/// block comes back as P0 and the third as P0 xor iv, which is the key.
+pub fn recover_key<F>(cipher_text: &[u8], mut check: F) -> Result<[u8; 16], Error>
+    where F: FnMut(&[u8]) -> Result<(), Error> {
+    if cipher_text.len() < 16 * 5 || cipher_text.len() % 16 != 0 {
+        return Err(Error::Generic("need at least five whole blocks of cipher text"));
+    }
+
+    let mut forged = cipher_text[..16].to_vec();
+    forged.extend(&[0; 16]);
+    forged.extend(&cipher_text[..16]);
+    forged.extend(&cipher_text[48..]);
+
+    let plain_text = match check(&forged) {
+        Err(Error::GenericStr(message)) if message.starts_with(ERROR_PREFIX) => parse_hex(&message[ERROR_PREFIX.len()..])?,
+        Err(e) => return Err(e),
+        Ok(()) => return Err(Error::Generic("the server accepted the forged cipher text")),
+    };
+
+    let mut key = [0; 16];
+    key.copy_from_slice(&xor(&plain_text[..16], &plain_text[32..48])?);
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::key_as_iv::{KeyAsIvServer, recover_key};
+
+    #[test]
+    fn ascii_passes_check() {
+        let server = KeyAsIvServer::new();
+
+        assert!(server.check(&server.encrypt(b"plain old ascii").unwrap()).is_ok());
+        assert!(server.check(&server.encrypt(&[0xff; 3]).unwrap()).is_err());
+    }
+
+    #[test]
+    fn recovers_key() {
+        let server = KeyAsIvServer::new();
+        let cipher_text = server.encrypt(&[b'A'; 16 * 5]).unwrap();
+
+        let key = recover_key(&cipher_text, |cipher_text| server.check(cipher_text)).unwrap();
+
+        assert_eq!(server.key, key);
+    }
+
+    #[test]
+    fn needs_five_blocks() {
+        let server = KeyAsIvServer::new();
+        let cipher_text = server.encrypt(&[b'A'; 16 * 3]).unwrap();
+
+        assert!(recover_key(&cipher_text, |cipher_text| server.check(cipher_text)).is_err());
+    }
+}
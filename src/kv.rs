@@ -0,0 +1,100 @@
+use crate::Error;
+use crate::hex::{parse_hex, to_hex};
+use std::str::from_utf8;
+
+// key=value pairs joined with &, like a query string. The metacharacters
+// & and = are percent escaped, and so is % itself so decoding is exact.
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' | '=' | '%' => {
+                escaped.push('%');
+                escaped.push_str(&to_hex(&[c as u8]));
+            },
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(s: &str) -> Result<String, Error> {
+    let bytes = s.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3).ok_or(Error::Generic("escape is cut short"))?;
+            unescaped.extend(parse_hex(hex)?);
+            i += 3;
+        } else {
+            unescaped.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    Ok(from_utf8(&unescaped)?.to_owned())
+}
+
+pub fn encode_kv(pairs: &[(&str, &str)]) -> String {
+    pairs.iter()
+        .map(|(k, v)| format!("{}={}", escape(k), escape(v)))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+pub fn parse_kv(input: &str) -> Result<Vec<(String, String)>, Error> {
+    if input.is_empty() {
+        return Ok(vec![]);
+    }
+
+    input.split('&').map(|pair| {
+        let mut parts = pair.splitn(2, '=');
+        let key = parts.next().unwrap();
+        let value = parts.next().ok_or(Error::Generic("pair is missing its ="))?;
+        if value.contains('=') {
+            return Err(Error::Generic("unescaped = in value"));
+        }
+
+        Ok((unescape(key)?, unescape(value)?))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kv::{encode_kv, parse_kv};
+
+    #[test]
+    fn parse_challenge_example() {
+        let result = parse_kv("foo=bar&baz=qux&zap=zazzle").unwrap();
+
+        assert_eq!(vec![("foo".to_owned(), "bar".to_owned()), ("baz".to_owned(), "qux".to_owned()), ("zap".to_owned(), "zazzle".to_owned())], result);
+    }
+
+    #[test]
+    fn encode_escapes_metacharacters() {
+        assert_eq!("email=foo@bar.com%26role%3dadmin&uid=10", encode_kv(&[("email", "foo@bar.com&role=admin"), ("uid", "10")]));
+    }
+
+    #[test]
+    fn roundtrip() {
+        let pairs = [("a&b", "c=d"), ("100%", ""), ("", "x%26y"), ("ünï", "cödé")];
+        let result = parse_kv(&encode_kv(&pairs)).unwrap();
+
+        assert_eq!(pairs.len(), result.len());
+        for ((k, v), (rk, rv)) in pairs.iter().zip(result.iter()) {
+            assert_eq!(k, rk);
+            assert_eq!(v, rv);
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(parse_kv("foo").is_err());
+        assert!(parse_kv("foo=bar=baz").is_err());
+        assert!(parse_kv("foo=ba%2").is_err());
+        assert!(parse_kv("foo=%zz").is_err());
+        assert!(parse_kv("").unwrap().is_empty());
+    }
+}
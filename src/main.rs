@@ -6,14 +6,50 @@ use crate::file::{file_to_vec, file_to_buf};
 use crate::string::{hamming_distance, trim_and_join};
 use std::str::from_utf8;
 use std::cmp::min;
-use crate::aes::{decrypt_aes_ecb, add_padding, decrypt_aes_cbc};
+use crate::aes::{Aes128, encrypt_ecb, decrypt_aes_ecb, decrypt_aes_cbc, decrypt_aes_ctr};
+use crate::padding::{add_padding, parse_padding, PaddingError};
+use crate::modes::CounterLayout;
+use crate::ctr_edit::{EditOracle, recover_plain_text};
+use crate::padding_oracle::{PaddingOracle, padding_oracle_decrypt, padding_oracle_encrypt};
+use crate::mode_oracle::detection_accuracy;
+use crate::byte_at_a_time::{EcbOracle, recover_suffix};
+use crate::fingerprint::fingerprint;
+use crate::cut_and_paste::{ProfileOracle, make_admin};
+use crate::bitflip::{CbcUserData, CtrUserData, inject_admin, inject_admin_ctr};
+use crate::key_as_iv::{KeyAsIvServer, recover_key};
+use crate::fixed_nonce::{encrypt_fixed_nonce, break_fixed_nonce, apply_key_stream};
+use crate::random::{random_key, random_u32};
+use crate::mt19937::{Mt19937, clone_mt19937};
+use crate::timestamp_seed::{timestamp_scenario, crack_timestamp_seed, unix_time};
+use crate::mt_cipher::{decrypt_mt, encrypt_with_random_prefix, recover_mt_key, password_reset_token, is_time_seeded_token};
 
 mod aes;
+mod aes_soft;
+#[cfg(target_arch = "x86_64")]
+mod aes_ni;
+mod modes;
+mod padding;
 mod hex;
 mod base64;
 mod xor;
 mod file;
 mod string;
+mod random;
+mod ctr_edit;
+mod fixed_nonce;
+mod crib;
+mod padding_oracle;
+mod mode_oracle;
+mod byte_at_a_time;
+mod fingerprint;
+mod kv;
+mod cut_and_paste;
+mod bitflip;
+mod key_as_iv;
+mod mt19937;
+mod timestamp_seed;
+mod mt_cipher;
+mod bench;
 
 #[derive(Debug)]
 pub enum Error {
@@ -21,6 +57,7 @@ pub enum Error {
     GenericStr(String),
     Utf8Error(std::str::Utf8Error),
     IoError(std::io::Error),
+    PaddingError(PaddingError),
 }
 
 impl From<std::str::Utf8Error> for Error {
@@ -35,6 +72,12 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<PaddingError> for Error {
+    fn from(err: PaddingError) -> Error {
+        Error::PaddingError(err)
+    }
+}
+
 fn number_of_duplicate_blocks(input: &[u8], key_size: usize) -> Result<usize, Error> {
     let mut parts = vec![];
     for i in 0..(input.len() / (key_size * 2)) {
@@ -83,11 +126,11 @@ fn score_edit_distance_16(input: &[Vec<u8>]) -> Result<usize, Error> {
     let mut edit_deltas: Vec<(usize, usize)> = vec![];
     for (i, buf) in input.iter().enumerate() {
         if !buf.is_empty() {
-            edit_deltas.push((number_of_duplicate_blocks(&buf, 16)?, i));
+            edit_deltas.push((number_of_duplicate_blocks(buf, 16)?, i));
         }
     }
 
-    edit_deltas.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+    edit_deltas.sort_unstable_by_key(|d| std::cmp::Reverse(d.0));
 
     Ok(edit_deltas[0].1)
 }
@@ -153,7 +196,7 @@ fn solve_1_2() -> Result<(), Error> {
 fn solve_1_3() -> Result<(), Error> {
     let expected = "Cooking MC's like a pound of bacon";
 
-    let result = guess_xor_byte_str(&std::str::from_utf8(&parse_hex("1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736")?)?);
+    let result = guess_xor_byte_str(std::str::from_utf8(&parse_hex("1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736")?)?);
 
     assert_eq!(expected, result);
 
@@ -179,7 +222,7 @@ fn solve_1_5() -> Result<(), Error> {
     let expected = "0b3637272a2b2e63622c2e69692a23693a2a3c6324202d623d63343c2a26226324272765272\
 a282b2f20430a652e2c652a3124333a653e2b2027630c692b20283165286326302e27282f";
     let result = to_hex(&xor_repeat(&"Burning 'em, if you ain't quick and nimble
-I go crazy when I hear a cymbal".bytes().collect::<Vec<u8>>(), &[b'I', b'C', b'E']));
+I go crazy when I hear a cymbal".bytes().collect::<Vec<u8>>(), b"ICE"));
 
     assert_eq!(expected, result);
 
@@ -221,7 +264,7 @@ fn solve_1_7() -> Result<(), Error> {
 fn solve_1_8() -> Result<(), Error> {
     let expected = 132;
 
-    let f:Vec<Vec<u8>> = file_to_vec("res/8.txt")?.iter().map(|s| parse_hex(&s)).collect::<Result<Vec<Vec<u8>>, Error>>()?;
+    let f:Vec<Vec<u8>> = file_to_vec("res/8.txt")?.iter().map(|s| parse_hex(s)).collect::<Result<Vec<Vec<u8>>, Error>>()?;
 
     let result = score_edit_distance_16(&f)?;
 
@@ -261,10 +304,289 @@ fn solve_2_10() -> Result<(), Error> {
     Ok(())
 }
 
+fn solve_2_11() -> Result<(), Error> {
+    let trials = 1000;
+    let accuracy = detection_accuracy(trials)?;
+
+    assert_eq!(1.0, accuracy);
+
+    println!("guessed the mode right in {}% of {} trials", accuracy * 100.0, trials);
+
+    Ok(())
+}
+
+fn challenge_12_secret() -> Result<Vec<u8>, Error> {
+    let f = file_to_buf("res/12.txt")?;
+    from_base64(&trim_and_join(from_utf8(&f)?))
+}
+
+fn solve_2_12() -> Result<(), Error> {
+    let expected = challenge_12_secret()?;
+
+    let oracle = EcbOracle::new(&expected);
+    let interim = recover_suffix(|input| oracle.encrypt(input))?;
+
+    let expected = from_utf8(&expected)?;
+    let result = from_utf8(&interim)?;
+
+    assert_eq!(expected, result);
+
+    println!("exp = {}\nres = {}", expected, result);
+
+    Ok(())
+}
+
+fn solve_2_13() -> Result<(), Error> {
+    let oracle = ProfileOracle::new();
+
+    let cipher_text = make_admin(|email| oracle.encrypt_profile(email))?;
+    let profile = oracle.decrypt_profile(&cipher_text)?;
+
+    assert!(profile.contains(&("role".to_owned(), "admin".to_owned())));
+
+    println!("{:?}", profile);
+
+    Ok(())
+}
+
+fn solve_2_14() -> Result<(), Error> {
+    let expected = challenge_12_secret()?;
+
+    let oracle = EcbOracle::with_random_prefix(&expected);
+    let interim = recover_suffix(|input| oracle.encrypt(input))?;
+
+    let expected = from_utf8(&expected)?;
+    let result = from_utf8(&interim)?;
+
+    assert_eq!(expected, result);
+
+    println!("exp = {}\nres = {}", expected, result);
+
+    Ok(())
+}
+
+fn solve_4_26() -> Result<(), Error> {
+    let server = CtrUserData::new();
+
+    let cipher_text = inject_admin_ctr(|user_data| server.encrypt(user_data))?;
+    let result = server.is_admin(&cipher_text)?;
+
+    assert!(result);
+
+    println!("admin = {}", result);
+
+    Ok(())
+}
+
+fn solve_4_27() -> Result<(), Error> {
+    let message = b"comment1=cooking%20MCs;userdata=YELLOW SUBMARINE;comment2=%20like%20a%20pound%20of%20bacon";
+    let server = KeyAsIvServer::new();
+    let cipher_text = server.encrypt(message)?;
+
+    let key = recover_key(&cipher_text, |cipher_text| server.check(cipher_text))?;
+    let result = decrypt_aes_cbc(&cipher_text, &key, &key)?;
+
+    assert_eq!(message.to_vec(), result);
+
+    println!("key = {}\nres = {}", to_hex(&key), from_utf8(&result)?);
+
+    Ok(())
+}
+
+fn fingerprint_oracles() -> Result<(), Error> {
+    let secret = challenge_12_secret()?;
+
+    let oracle = EcbOracle::new(&secret);
+    println!("challenge 12 oracle\n{}\n", fingerprint(|input| oracle.encrypt(input))?);
+
+    let oracle = EcbOracle::with_random_prefix(&secret);
+    println!("challenge 14 oracle\n{}", fingerprint(|input| oracle.encrypt(input))?);
+
+    Ok(())
+}
+
+/// fingerprints ECB with a known prefix and suffix under the named padding
+fn fingerprint_padding(name: &str) -> Result<(), Error> {
+    let padding = parse_padding(name)?;
+    let cipher = Aes128::new(&random_key());
+
+    let result = fingerprint(|input| encrypt_ecb(&cipher, &[b"prefix", input, b"suffix"].concat(), padding))?;
+    println!("ecb with {:?} padding, 6 byte prefix and suffix\n{}", padding, result);
+
+    Ok(())
+}
+
+fn solve_2_16() -> Result<(), Error> {
+    let server = CbcUserData::new();
+
+    let cipher_text = inject_admin(|user_data| server.encrypt(user_data))?;
+    let result = server.is_admin(&cipher_text)?;
+
+    assert!(result);
+
+    println!("admin = {}", result);
+
+    Ok(())
+}
+
+fn base64_lines(path: &str) -> Result<Vec<Vec<u8>>, Error> {
+    file_to_vec(path)?.iter().filter(|s| !s.is_empty()).map(|s| from_base64(s)).collect()
+}
+
+fn challenge_17_strings() -> Result<Vec<Vec<u8>>, Error> {
+    base64_lines("res/17.txt")
+}
+
+fn solve_3_17() -> Result<(), Error> {
+    let strings = challenge_17_strings()?;
+    let oracle = PaddingOracle::new(&strings)?;
+
+    let result = padding_oracle_decrypt(oracle.iv(), oracle.cipher_text(), |iv, cipher_text| oracle.padding_valid(iv, cipher_text))?;
+
+    assert!(strings.contains(&result.plain_text));
+
+    println!("{} ({} queries)", from_utf8(&result.plain_text)?, result.queries);
+
+    // the same oracle also encrypts, a message the server never saw
+    // decrypts fine under its key
+    let message = b"forged without the key";
+    let forged = padding_oracle_encrypt(message, |iv, cipher_text| oracle.padding_valid(iv, cipher_text))?;
+    let result = padding_oracle_decrypt(&forged.iv, &forged.cipher_text, |iv, cipher_text| oracle.padding_valid(iv, cipher_text))?;
+
+    assert_eq!(message.to_vec(), result.plain_text);
+
+    println!("{} ({} queries)", from_utf8(&result.plain_text)?, forged.queries);
+
+    Ok(())
+}
+
+fn solve_3_18() -> Result<(), Error> {
+    let expected = "Yo, VIP Let's kick it Ice, Ice, baby Ice, Ice, baby ";
+    let key: [u8; 16] = [b'Y', b'E', b'L', b'L', b'O', b'W', b' ', b'S', b'U', b'B', b'M', b'A', b'R', b'I', b'N', b'E'];
+
+    let interim = decrypt_aes_ctr(&from_base64("L77na/nrFsKvynd6HzOoG7GHTLXsTVu9qvY/2syLXzhPweyyMTJULu/6/kXX0KSvoOLSFQ==")?, &key, &CounterLayout::LittleEndian64(0))?;
+    let result = from_utf8(&interim)?;
+
+    assert_eq!(expected, result);
+
+    println!("exp = {}\nres = {}", expected, result);
+
+    Ok(())
+}
+
+/// breaks fixed nonce CTR over the plain texts, the recovered ones are as
+/// long as the key stream goes. A column of nothing but letters reads the
+/// same xored with 0x20, so its case can't be told.
+fn break_fixed_nonce_lines(plain_texts: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+    let cipher_texts = encrypt_fixed_nonce(plain_texts, &random_key())?;
+
+    let key_stream = break_fixed_nonce(&cipher_texts)?;
+
+    cipher_texts.iter().map(|c| apply_key_stream(c, &key_stream)).collect()
+}
+
+fn solve_3_19() -> Result<(), Error> {
+    let plain_texts = base64_lines("res/19.txt")?;
+    let results = break_fixed_nonce_lines(&plain_texts)?;
+
+    for (expected, result) in plain_texts.iter().zip(results.iter()) {
+        let expected = &expected[..result.len()];
+        assert!(expected.eq_ignore_ascii_case(result));
+
+        println!("exp = {}\nres = {}", from_utf8(expected)?, from_utf8(result)?);
+    }
+
+    Ok(())
+}
+
+fn solve_3_21() -> Result<(), Error> {
+    let expected = [3499211612, 581869302, 3890346734, 3586334585, 545404204];
+    let mut mt = Mt19937::new(5489);
+    let result = [mt.extract(), mt.extract(), mt.extract(), mt.extract(), mt.extract()];
+
+    assert_eq!(expected, result);
+
+    println!("exp = {:?}\nres = {:?}", expected, result);
+
+    Ok(())
+}
+
+fn solve_3_22() -> Result<(), Error> {
+    let scenario = timestamp_scenario(unix_time, 40..=1000)?;
+
+    let result = crack_timestamp_seed(scenario.output, scenario.finished_at - 2000..=scenario.finished_at)?;
+
+    assert_eq!(scenario.seed, result.seed);
+
+    println!("seed = {} ({} candidates)", result.seed, result.candidates);
+
+    Ok(())
+}
+
+fn solve_3_23() -> Result<(), Error> {
+    let mut mt = Mt19937::new(random_u32());
+    let outputs = (0..624).map(|_| mt.extract()).collect::<Vec<u32>>();
+
+    let mut cloned = clone_mt19937(&outputs)?;
+    let expected = (0..5).map(|_| mt.extract()).collect::<Vec<u32>>();
+    let result = (0..5).map(|_| cloned.extract()).collect::<Vec<u32>>();
+
+    assert_eq!(expected, result);
+
+    println!("exp = {:?}\nres = {:?}", expected, result);
+
+    Ok(())
+}
+
+fn solve_3_24() -> Result<(), Error> {
+    let known = [b'A'; 14];
+    let expected = random_u32() as u16;
+    let cipher_text = encrypt_with_random_prefix(&known, expected)?;
+
+    let result = recover_mt_key(&cipher_text, &known)?;
+
+    assert_eq!(expected, result);
+    assert!(decrypt_mt(&cipher_text, result)?.ends_with(&known));
+
+    println!("exp = {}\nres = {}", expected, result);
+
+    let token = password_reset_token(unix_time)?;
+    assert!(is_time_seeded_token(&token, unix_time()?, 3600));
+
+    println!("reset token {} is time seeded", to_hex(&token));
+
+    Ok(())
+}
+
+fn solve_4_25() -> Result<(), Error> {
+    let key: [u8; 16] = [b'Y', b'E', b'L', b'L', b'O', b'W', b' ', b'S', b'U', b'B', b'M', b'A', b'R', b'I', b'N', b'E'];
+
+    let f = file_to_buf("res/25.txt")?;
+    let b64 = from_utf8(&f)?;
+    let expected = decrypt_aes_ecb(&from_base64(&trim_and_join(b64))?, &key)?;
+
+    let oracle = EditOracle::new(&expected)?;
+    let interim = recover_plain_text(oracle.cipher_text(), |offset, new_text| oracle.edit(offset, new_text))?;
+
+    let expected = from_utf8(&expected)?;
+    let result = from_utf8(&interim)?;
+
+    assert_eq!(expected, result);
+
+    println!("exp = {}\nres = {}", expected, result);
+
+    Ok(())
+}
+
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
-    if args.len() != 2 {
+    let arguments = match args.get(1).map(|a| a.as_str()) {
+        Some("crib") => 3..=3,
+        Some("fingerprint") => 2..=3,
+        _ => 2..=2,
+    };
+    if !arguments.contains(&args.len()) {
         eprintln!("wrong number of arguments");
         return;
     }
@@ -300,6 +622,63 @@ fn main() {
         "2.10" => {
             solve_2_10().unwrap();
         },
+        "2.11" => {
+            solve_2_11().unwrap();
+        },
+        "2.12" => {
+            solve_2_12().unwrap();
+        },
+        "2.13" => {
+            solve_2_13().unwrap();
+        },
+        "2.14" => {
+            solve_2_14().unwrap();
+        },
+        "2.16" => {
+            solve_2_16().unwrap();
+        },
+        "3.17" => {
+            solve_3_17().unwrap();
+        },
+        "3.18" => {
+            solve_3_18().unwrap();
+        },
+        "3.19" => {
+            solve_3_19().unwrap();
+        },
+        "3.21" => {
+            solve_3_21().unwrap();
+        },
+        "3.22" => {
+            solve_3_22().unwrap();
+        },
+        "3.23" => {
+            solve_3_23().unwrap();
+        },
+        "3.24" => {
+            solve_3_24().unwrap();
+        },
+        "4.25" => {
+            solve_4_25().unwrap();
+        },
+        "crib" => {
+            crib::run(&args[2]).unwrap();
+        },
+        "fingerprint" => {
+            match args.get(2) {
+                Some(name) => fingerprint_padding(name).unwrap(),
+                None => fingerprint_oracles().unwrap(),
+            }
+        },
+        "4.26" => {
+            solve_4_26().unwrap();
+        },
+        "4.27" => {
+            solve_4_27().unwrap();
+        },
+        "bench" => {
+            bench::run().unwrap();
+        },
         _ => {
             eprintln!("unknown argument")
         }
@@ -315,7 +694,23 @@ mod tests {
     use crate::{chop_and_transpose, decrypt_buf};
     use std::str::from_utf8;
     use crate::string::trim_and_join;
-    use crate::aes::{decrypt_aes_ecb, add_padding, decrypt_aes_cbc};
+    use crate::aes::{decrypt_aes_ecb, decrypt_aes_cbc, encrypt_aes_ctr, decrypt_aes_ctr};
+    use crate::padding::add_padding;
+    use crate::modes::CounterLayout;
+    use crate::ctr_edit::{EditOracle, recover_plain_text};
+    use crate::padding_oracle::{PaddingOracle, padding_oracle_decrypt};
+    use crate::challenge_17_strings;
+    use crate::mode_oracle::detection_accuracy;
+    use crate::byte_at_a_time::{EcbOracle, recover_suffix};
+    use crate::challenge_12_secret;
+    use crate::cut_and_paste::{ProfileOracle, make_admin};
+    use crate::bitflip::{CbcUserData, CtrUserData, inject_admin, inject_admin_ctr};
+    use crate::key_as_iv::{KeyAsIvServer, recover_key};
+    use crate::{base64_lines, break_fixed_nonce_lines};
+    use crate::mt19937::{Mt19937, clone_mt19937};
+    use crate::timestamp_seed::{timestamp_scenario, crack_timestamp_seed};
+    use crate::mt_cipher::{encrypt_with_random_prefix, recover_mt_key, password_reset_token, is_time_seeded_token};
+    use crate::random::random_u32;
 
     #[test]
     fn test_chop_and_transpose_aligned() {
@@ -383,7 +778,7 @@ mod tests {
     #[test]
     fn crypto_pals_challenge3_complete() {
         assert_eq!("Cooking MC's like a pound of bacon",
-                   guess_xor_byte_str(&std::str::from_utf8(&parse_hex("1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736").unwrap()).unwrap()));
+                   guess_xor_byte_str(std::str::from_utf8(&parse_hex("1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736").unwrap()).unwrap()));
     }
 
     #[test]
@@ -399,7 +794,7 @@ mod tests {
         assert_eq!("0b3637272a2b2e63622c2e69692a23693a2a3c6324202d623d63343c2a26226324272765272\
 a282b2f20430a652e2c652a3124333a653e2b2027630c692b20283165286326302e27282f",
             to_hex(&xor_repeat(&"Burning 'em, if you ain't quick and nimble
-I go crazy when I hear a cymbal".bytes().collect::<Vec<u8>>(), &vec![b'I', b'C', b'E'])));
+I go crazy when I hear a cymbal".bytes().collect::<Vec<u8>>(), b"ICE")));
     }
 
     #[test]
@@ -446,4 +841,176 @@ I go crazy when I hear a cymbal".bytes().collect::<Vec<u8>>(), &vec![b'I', b'C',
         assert_eq!("I'm back and I'm ringin' the bell \nA rockin' on the mike while the fly girls yell \nIn ecstasy in the back of me \nWell that's my DJ Deshay cuttin' all them Z's \nHittin' hard and the girlies goin' crazy \nVanilla's on the mike, man I'm not lazy. \n\nI'm lettin' my drug kick in \nIt controls my mouth and I begin \nTo just let it flow, let my concepts go \nMy posse's to the side yellin', Go Vanilla Go! \n\nSmooth 'cause that's the way I will be \nAnd if you don't give a damn, then \nWhy you starin' at me \nSo get off 'cause I control the stage \nThere's no dissin' allowed \nI'm in my own phase \nThe girlies sa y they love me and that is ok \nAnd I can dance better than any kid n' play \n\nStage 2 -- Yea the one ya' wanna listen to \nIt's off my head so let the beat play through \nSo I can funk it up and make it sound good \n1-2-3 Yo -- Knock on some wood \nFor good luck, I like my rhymes atrocious \nSupercalafragilisticexpialidocious \nI'm an effect and that you can bet \nI can take a fly girl and make her wet. \n\nI'm like Samson -- Samson to Delilah \nThere's no denyin', You can try to hang \nBut you'll keep tryin' to get my style \nOver and over, practice makes perfect \nBut not if you're a loafer. \n\nYou'll get nowhere, no place, no time, no girls \nSoon -- Oh my God, homebody, you probably eat \nSpaghetti with a spoon! Come on and say it! \n\nVIP. Vanilla Ice yep, yep, I'm comin' hard like a rhino \nIntoxicating so you stagger like a wino \nSo punks stop trying and girl stop cryin' \nVanilla Ice is sellin' and you people are buyin' \n'Cause why the freaks are jockin' like Crazy Glue \nMovin' and groovin' trying to sing along \nAll through the ghetto groovin' this here song \nNow you're amazed by the VIP posse. \n\nSteppin' so hard like a German Nazi \nStartled by the bases hittin' ground \nThere's no trippin' on mine, I'm just gettin' down \nSparkamatic, I'm hangin' tight like a fanatic \nYou trapped me once and I thought that \nYou might have it \nSo step down and lend me your ear \n'89 in my time! You, '90 is my year. \n\nYou're weakenin' fast, YO! and I can tell it \nYour body's gettin' hot, so, so I can smell it \nSo don't be mad and don't be sad \n'Cause the lyrics belong to ICE, You can call me Dad \nYou're pitchin' a fit, so step back and endure \nLet the witch doctor, Ice, do the dance to cure \nSo come up close and don't be square \nYou wanna battle me -- Anytime, anywhere \n\nYou thought that I was weak, Boy, you're dead wrong \nSo come on, everybody and sing this song \n\nSay -- Play that funky music Say, go white boy, go white boy go \nplay that funky music Go white boy, go white boy, go \nLay down and boogie and play that funky music till you die. \n\nPlay that funky music Come on, Come on, let me hear \nPlay that funky music white boy you say it, say it \nPlay that funky music A little louder now \nPlay that funky music, white boy Come on, Come on, Come on \nPlay that funky music \n",
                    result);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn crypto_pals_challenge11_complete() {
+        assert_eq!(1.0, detection_accuracy(1000).unwrap());
+    }
+
+    #[test]
+    fn crypto_pals_challenge12_complete() {
+        let expected = challenge_12_secret().unwrap();
+        assert!(expected.starts_with(b"Rollin' in my 5.0\n"));
+
+        let oracle = EcbOracle::new(&expected);
+        let result = recover_suffix(|input| oracle.encrypt(input)).unwrap();
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn crypto_pals_challenge13_complete() {
+        let oracle = ProfileOracle::new();
+
+        let cipher_text = make_admin(|email| oracle.encrypt_profile(email)).unwrap();
+        let profile = oracle.decrypt_profile(&cipher_text).unwrap();
+
+        assert_eq!(("role".to_owned(), "admin".to_owned()), profile[profile.len() - 1]);
+    }
+
+    #[test]
+    fn crypto_pals_challenge14_complete() {
+        let expected = challenge_12_secret().unwrap();
+
+        for _ in 0..5 {
+            let oracle = EcbOracle::with_random_prefix(&expected);
+            let result = recover_suffix(|input| oracle.encrypt(input)).unwrap();
+
+            assert_eq!(expected, result);
+        }
+    }
+
+    #[test]
+    fn crypto_pals_challenge16_complete() {
+        let server = CbcUserData::new();
+
+        let cipher_text = inject_admin(|user_data| server.encrypt(user_data)).unwrap();
+
+        assert!(server.is_admin(&cipher_text).unwrap());
+    }
+
+    #[test]
+    fn crypto_pals_challenge18_complete() {
+        let key: [u8; 16] = [b'Y', b'E', b'L', b'L', b'O', b'W', b' ', b'S', b'U', b'B', b'M', b'A', b'R', b'I', b'N', b'E'];
+        let interim = decrypt_aes_ctr(&from_base64("L77na/nrFsKvynd6HzOoG7GHTLXsTVu9qvY/2syLXzhPweyyMTJULu/6/kXX0KSvoOLSFQ==").unwrap(), &key, &CounterLayout::LittleEndian64(0)).unwrap();
+        let result = from_utf8(&interim).unwrap();
+
+        assert_eq!("Yo, VIP Let's kick it Ice, Ice, baby Ice, Ice, baby ", result);
+    }
+
+    #[test]
+    fn ctr_roundtrip_any_length() {
+        let key: [u8; 16] = [b'Y', b'E', b'L', b'L', b'O', b'W', b' ', b'S', b'U', b'B', b'M', b'A', b'R', b'I', b'N', b'E'];
+
+        for length in 0..50 {
+            let clean = vec![b'q'; length];
+
+            let enc = encrypt_aes_ctr(&clean, &key, &CounterLayout::LittleEndian64(42)).unwrap();
+            assert_eq!(length, enc.len());
+
+            assert_eq!(clean, decrypt_aes_ctr(&enc, &key, &CounterLayout::LittleEndian64(42)).unwrap());
+        }
+    }
+
+    #[test]
+    fn crypto_pals_challenge25_complete() {
+        let key: [u8; 16] = [b'Y', b'E', b'L', b'L', b'O', b'W', b' ', b'S', b'U', b'B', b'M', b'A', b'R', b'I', b'N', b'E'];
+        let f = file_to_buf("res/25.txt").unwrap();
+        let b64 = from_utf8(&f).unwrap();
+        let expected = decrypt_aes_ecb(&from_base64(&trim_and_join(b64)).unwrap(), &key).unwrap();
+
+        let oracle = EditOracle::new(&expected).unwrap();
+        let result = recover_plain_text(oracle.cipher_text(), |offset, new_text| oracle.edit(offset, new_text)).unwrap();
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn crypto_pals_challenge19_complete() {
+        let plain_texts = base64_lines("res/19.txt").unwrap();
+        assert_eq!(40, plain_texts.len());
+
+        let results = break_fixed_nonce_lines(&plain_texts).unwrap();
+
+        for (expected, result) in plain_texts.iter().zip(results.iter()) {
+            assert_eq!(20, result.len());
+            assert!(expected[..20].eq_ignore_ascii_case(result));
+        }
+    }
+
+    #[test]
+    fn crypto_pals_challenge21_complete() {
+        let mut mt = Mt19937::new(5489);
+
+        assert_eq!(3499211612, mt.extract());
+        assert_eq!(581869302, mt.extract());
+    }
+
+    #[test]
+    fn crypto_pals_challenge22_complete() {
+        let scenario = timestamp_scenario(|| Ok(1_600_000_000), 40..=1000).unwrap();
+
+        let result = crack_timestamp_seed(scenario.output, scenario.finished_at - 2000..=scenario.finished_at).unwrap();
+
+        assert_eq!(scenario.seed, result.seed);
+        assert!(result.candidates <= 2001);
+    }
+
+    #[test]
+    fn crypto_pals_challenge23_complete() {
+        let mut mt = Mt19937::new(random_u32());
+        let outputs = (0..624).map(|_| mt.extract()).collect::<Vec<u32>>();
+
+        let mut cloned = clone_mt19937(&outputs).unwrap();
+
+        for _ in 0..2000 {
+            assert_eq!(mt.extract(), cloned.extract());
+        }
+    }
+
+    #[test]
+    fn crypto_pals_challenge24_complete() {
+        let known = [b'A'; 14];
+        let key = random_u32() as u16;
+        let cipher_text = encrypt_with_random_prefix(&known, key).unwrap();
+
+        assert_eq!(key, recover_mt_key(&cipher_text, &known).unwrap());
+
+        let token = password_reset_token(|| Ok(1_600_000_000)).unwrap();
+        assert!(is_time_seeded_token(&token, 1_600_000_100, 3600));
+    }
+
+    #[test]
+    fn crypto_pals_challenge17_complete() {
+        let strings = challenge_17_strings().unwrap();
+        assert_eq!(10, strings.len());
+
+        for _ in 0..5 {
+            let oracle = PaddingOracle::new(&strings).unwrap();
+
+            let result = padding_oracle_decrypt(oracle.iv(), oracle.cipher_text(), |iv, cipher_text| oracle.padding_valid(iv, cipher_text)).unwrap();
+
+            assert!(strings.contains(&result.plain_text));
+        }
+    }
+
+    #[test]
+    fn crypto_pals_challenge26_complete() {
+        let server = CtrUserData::new();
+
+        let cipher_text = inject_admin_ctr(|user_data| server.encrypt(user_data)).unwrap();
+
+        assert!(server.is_admin(&cipher_text).unwrap());
+    }
+
+    #[test]
+    fn crypto_pals_challenge27_complete() {
+        let message = b"comment1=cooking%20MCs;userdata=YELLOW SUBMARINE;comment2=%20like%20a%20pound%20of%20bacon";
+        let server = KeyAsIvServer::new();
+        let cipher_text = server.encrypt(message).unwrap();
+
+        let key = recover_key(&cipher_text, |cipher_text| server.check(cipher_text)).unwrap();
+
+        assert_eq!(message.to_vec(), decrypt_aes_cbc(&cipher_text, &key, &key).unwrap());
+    }
+}
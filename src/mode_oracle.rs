@@ -0,0 +1,94 @@
+use crate::Error;
+use crate::number_of_duplicate_blocks;
+use crate::aes::{encrypt_aes_ecb, encrypt_aes_cbc};
+use crate::random::{random_bytes, random_key, random_range};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Ecb,
+    Cbc,
+}
+
+/// The challenge 11 oracle, 5 to 10 random bytes on each side of the input
+/// and then ECB or CBC under a fresh key. The mode is handed back as well so
+/// that guesses can be scored.
+pub fn encryption_oracle(input: &[u8]) -> Result<(Vec<u8>, Mode), Error> {
+    let mut plain_text = random_bytes(random_range(5, 11));
+    plain_text.extend(input);
+    plain_text.extend(random_bytes(random_range(5, 11)));
+
+    let key = random_key();
+    if random_range(0, 2) == 0 {
+        Ok((encrypt_aes_ecb(&plain_text, &key)?, Mode::Ecb))
+    } else {
+        Ok((encrypt_aes_cbc(&plain_text, &key, &random_key())?, Mode::Cbc))
+    }
+}
+
+/// Enough identical bytes that, whatever the prefix length, the blocks
+/// number_of_duplicate_blocks looks at include at least two full copies.
+pub fn detection_input() -> Vec<u8> {
+    vec![b'A'; 16 * 5]
+}
+
+/// Guesses the mode from a cipher text of detection_input, ECB turns the
+/// repeated plain text blocks into repeated cipher text blocks.
+pub fn detect_mode(cipher_text: &[u8]) -> Result<Mode, Error> {
+    if number_of_duplicate_blocks(cipher_text, 16)? > 0 {
+        Ok(Mode::Ecb)
+    } else {
+        Ok(Mode::Cbc)
+    }
+}
+
+/// Runs the oracle trials times and returns the fraction of correct guesses.
+pub fn detection_accuracy(trials: usize) -> Result<f64, Error> {
+    let input = detection_input();
+    let mut correct = 0;
+
+    for _ in 0..trials {
+        let (cipher_text, mode) = encryption_oracle(&input)?;
+        if detect_mode(&cipher_text)? == mode {
+            correct += 1;
+        }
+    }
+
+    Ok(correct as f64 / trials as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mode_oracle::{Mode, encryption_oracle, detect_mode, detection_accuracy, detection_input};
+
+    #[test]
+    fn oracle_output_length() {
+        for _ in 0..100 {
+            let (cipher_text, _) = encryption_oracle(b"YELLOW SUBMARINE").unwrap();
+
+            assert_eq!(0, cipher_text.len() % 16);
+            assert!(cipher_text.len() >= 32 && cipher_text.len() <= 48);
+        }
+    }
+
+    #[test]
+    fn oracle_uses_both_modes() {
+        let modes: Vec<Mode> = (0..100).map(|_| encryption_oracle(b"").unwrap().1).collect();
+
+        assert!(modes.contains(&Mode::Ecb));
+        assert!(modes.contains(&Mode::Cbc));
+    }
+
+    #[test]
+    fn detect_over_many_trials() {
+        let accuracy = detection_accuracy(5000).unwrap();
+
+        assert_eq!(1.0, accuracy, "accuracy over 5000 trials");
+    }
+
+    #[test]
+    fn detect_needs_repeated_input() {
+        let (cipher_text, mode) = encryption_oracle(&detection_input()).unwrap();
+
+        assert_eq!(mode, detect_mode(&cipher_text).unwrap());
+    }
+}
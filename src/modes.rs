@@ -0,0 +1,527 @@
+use crate::Error;
+use crate::xor::xor_in_place;
+
+/// A keyed block cipher, the modes below only need to be written once on
+/// top of this and work with any cipher that implements it.
+pub trait BlockCipher {
+    fn block_size(&self) -> usize;
+
+    /// block is exactly block_size() long
+    fn encrypt_block(&self, block: &mut [u8]);
+
+    /// block is exactly block_size() long
+    fn decrypt_block(&self, block: &mut [u8]);
+
+    /// buf is a whole number of blocks, ciphers that can keep several
+    /// independent blocks in flight override this
+    fn encrypt_blocks(&self, buf: &mut [u8]) {
+        for block in buf.chunks_exact_mut(self.block_size()) {
+            self.encrypt_block(block);
+        }
+    }
+
+    /// buf is a whole number of blocks, ciphers that can keep several
+    /// independent blocks in flight override this
+    fn decrypt_blocks(&self, buf: &mut [u8]) {
+        for block in buf.chunks_exact_mut(self.block_size()) {
+            self.decrypt_block(block);
+        }
+    }
+}
+
+/// Number of blocks handed to encrypt_blocks/decrypt_blocks at a time by the
+/// modes that have to stage them in a scratch buffer.
+const BATCH_BLOCKS: usize = 8;
+
+/// The chaining modes keep their state in fixed buffers on the stack so
+/// they never allocate, which caps the block size they work with.
+const MAX_BLOCK_SIZE: usize = 16;
+
+fn check_block_multiple(len: usize, block_size: usize) -> Result<(), Error> {
+    if len % block_size != 0 {
+        return Err(Error::Generic("buffer isn't a multiple of the block size"));
+    }
+    Ok(())
+}
+
+fn check_iv(iv: &[u8], block_size: usize) -> Result<(), Error> {
+    if block_size > MAX_BLOCK_SIZE {
+        return Err(Error::Generic("block size is too large for the chaining modes"));
+    }
+    if iv.len() != block_size {
+        return Err(Error::Generic("iv must be one block long"));
+    }
+    Ok(())
+}
+
+pub fn ecb_encrypt<C: BlockCipher>(cipher: &C, buf: &mut [u8]) -> Result<(), Error> {
+    check_block_multiple(buf.len(), cipher.block_size())?;
+
+    cipher.encrypt_blocks(buf);
+
+    Ok(())
+}
+
+pub fn ecb_decrypt<C: BlockCipher>(cipher: &C, buf: &mut [u8]) -> Result<(), Error> {
+    check_block_multiple(buf.len(), cipher.block_size())?;
+
+    cipher.decrypt_blocks(buf);
+
+    Ok(())
+}
+
+pub fn cbc_encrypt<C: BlockCipher>(cipher: &C, iv: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+    check_block_multiple(buf.len(), cipher.block_size())?;
+    check_iv(iv, cipher.block_size())?;
+
+    let mut previous = [0u8; MAX_BLOCK_SIZE];
+    let previous = &mut previous[..iv.len()];
+    previous.copy_from_slice(iv);
+    for block in buf.chunks_exact_mut(cipher.block_size()) {
+        xor_in_place(block, previous);
+        cipher.encrypt_block(block);
+
+        previous.copy_from_slice(block);
+    }
+
+    Ok(())
+}
+
+pub fn cbc_decrypt<C: BlockCipher>(cipher: &C, iv: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+    check_block_multiple(buf.len(), cipher.block_size())?;
+    check_iv(iv, cipher.block_size())?;
+
+    // the blocks decrypt independently, only the xor needs the cipher text
+    // of the block before, so a batch is saved off and decrypted in one go
+    let block_size = cipher.block_size();
+    let mut previous = [0u8; MAX_BLOCK_SIZE];
+    let previous = &mut previous[..block_size];
+    previous.copy_from_slice(iv);
+    let mut cipher_text = [0u8; MAX_BLOCK_SIZE * BATCH_BLOCKS];
+    for chunk in buf.chunks_mut(block_size * BATCH_BLOCKS) {
+        let saved = &mut cipher_text[..chunk.len()];
+        saved.copy_from_slice(chunk);
+
+        cipher.decrypt_blocks(chunk);
+
+        xor_in_place(&mut chunk[..block_size], previous);
+        xor_in_place(&mut chunk[block_size..], saved);
+
+        previous.copy_from_slice(&saved[saved.len() - block_size..]);
+    }
+
+    Ok(())
+}
+
+/// Full block cipher feedback, the last block may be partial so no padding
+/// is needed.
+pub fn cfb_encrypt<C: BlockCipher>(cipher: &C, iv: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+    check_iv(iv, cipher.block_size())?;
+
+    let mut register = [0u8; MAX_BLOCK_SIZE];
+    let register = &mut register[..iv.len()];
+    register.copy_from_slice(iv);
+    for block in buf.chunks_mut(cipher.block_size()) {
+        cipher.encrypt_block(register);
+        xor_in_place(block, register);
+
+        register[..block.len()].copy_from_slice(block);
+    }
+
+    Ok(())
+}
+
+pub fn cfb_decrypt<C: BlockCipher>(cipher: &C, iv: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+    check_iv(iv, cipher.block_size())?;
+
+    let block_size = cipher.block_size();
+    let mut register = [0u8; MAX_BLOCK_SIZE];
+    let mut cipher_block = [0u8; MAX_BLOCK_SIZE];
+    register[..block_size].copy_from_slice(iv);
+    for block in buf.chunks_mut(block_size) {
+        cipher_block[..block.len()].copy_from_slice(block);
+
+        cipher.encrypt_block(&mut register[..block_size]);
+        xor_in_place(block, &register);
+
+        std::mem::swap(&mut register, &mut cipher_block);
+    }
+
+    Ok(())
+}
+
+/// Output feedback, encryption and decryption are the same operation.
+pub fn ofb_encrypt<C: BlockCipher>(cipher: &C, iv: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+    check_iv(iv, cipher.block_size())?;
+
+    let mut register = [0u8; MAX_BLOCK_SIZE];
+    let register = &mut register[..iv.len()];
+    register.copy_from_slice(iv);
+    for block in buf.chunks_mut(cipher.block_size()) {
+        cipher.encrypt_block(register);
+        xor_in_place(block, register);
+    }
+
+    Ok(())
+}
+
+pub fn ofb_decrypt<C: BlockCipher>(cipher: &C, iv: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+    ofb_encrypt(cipher, iv, buf)
+}
+
+/// How the nonce and block counter are packed into the counter block.
+#[derive(Clone, Copy, Debug)]
+pub enum CounterLayout {
+    /// cryptopals style, a 64 bit little endian nonce followed by a 64 bit
+    /// little endian block counter starting at 0
+    LittleEndian64(u64),
+    /// NIST SP 800-38A style, the whole block is one 128 bit big endian
+    /// counter starting at the given block, nothing in the challenges uses
+    /// it so it is only built for the tests
+    #[cfg(test)]
+    BigEndian128([u8; 16]),
+}
+
+impl CounterLayout {
+    pub fn counter_block(&self, block_index: u64) -> [u8; 16] {
+        let mut block = [0u8; 16];
+        match self {
+            CounterLayout::LittleEndian64(nonce) => {
+                block[..8].copy_from_slice(&nonce.to_le_bytes());
+                block[8..].copy_from_slice(&block_index.to_le_bytes());
+            },
+            #[cfg(test)]
+            CounterLayout::BigEndian128(initial) => {
+                let counter = u128::from_be_bytes(*initial).wrapping_add(block_index as u128);
+                block.copy_from_slice(&counter.to_be_bytes());
+            },
+        }
+        block
+    }
+}
+
+/// CTR key stream that can be positioned at any byte offset without
+/// generating the blocks before it.
+pub struct CtrKeyStream<'a, C: BlockCipher> {
+    cipher: &'a C,
+    layout: CounterLayout,
+    position: u64,
+    cached: Option<(u64, [u8; 16])>,
+}
+
+impl<'a, C: BlockCipher> CtrKeyStream<'a, C> {
+    pub fn new(cipher: &'a C, layout: CounterLayout) -> Result<CtrKeyStream<'a, C>, Error> {
+        if cipher.block_size() != 16 {
+            return Err(Error::Generic("ctr needs a 16 byte block cipher"));
+        }
+
+        Ok(CtrKeyStream { cipher, layout, position: 0, cached: None })
+    }
+
+    fn key_stream_block(&mut self, block_index: u64) -> [u8; 16] {
+        match self.cached {
+            Some((index, block)) if index == block_index => block,
+            _ => {
+                let mut block = self.layout.counter_block(block_index);
+                self.cipher.encrypt_block(&mut block);
+                self.cached = Some((block_index, block));
+                block
+            },
+        }
+    }
+
+    pub fn seek(&mut self, offset: u64) {
+        self.position = offset;
+    }
+
+    /// xors the key stream into buf, starting at the current position
+    pub fn apply(&mut self, buf: &mut [u8]) {
+        let mut batch = [0u8; 16 * BATCH_BLOCKS];
+        let mut done = 0;
+
+        while done < buf.len() {
+            let offset = (self.position % 16) as usize;
+            let block_index = self.position / 16;
+            let remaining = buf.len() - done;
+
+            let take = if offset == 0 && remaining >= 16 {
+                // whole blocks, the counters are independent so they are
+                // encrypted a batch at a time
+                let blocks = (remaining / 16).min(BATCH_BLOCKS);
+                for (i, counter) in batch.chunks_exact_mut(16).take(blocks).enumerate() {
+                    counter.copy_from_slice(&self.layout.counter_block(block_index + i as u64));
+                }
+                self.cipher.encrypt_blocks(&mut batch[..blocks * 16]);
+
+                xor_in_place(&mut buf[done..done + blocks * 16], &batch);
+                blocks * 16
+            } else {
+                let block = self.key_stream_block(block_index);
+                let take = (16 - offset).min(remaining);
+
+                xor_in_place(&mut buf[done..done + take], &block[offset..]);
+                take
+            };
+
+            done += take;
+            self.position += take as u64;
+        }
+    }
+}
+
+/// Counter mode, works on any length and encryption and decryption are the
+/// same operation.
+pub fn ctr_encrypt<C: BlockCipher>(cipher: &C, layout: &CounterLayout, buf: &mut [u8]) -> Result<(), Error> {
+    CtrKeyStream::new(cipher, *layout)?.apply(buf);
+
+    Ok(())
+}
+
+pub fn ctr_decrypt<C: BlockCipher>(cipher: &C, layout: &CounterLayout, buf: &mut [u8]) -> Result<(), Error> {
+    ctr_encrypt(cipher, layout, buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modes::{BlockCipher, CounterLayout, ecb_encrypt, ecb_decrypt, cbc_encrypt, cbc_decrypt, cfb_encrypt, cfb_decrypt, ofb_encrypt, ofb_decrypt, ctr_encrypt, ctr_decrypt, CtrKeyStream};
+    use crate::aes::{Aes, encrypt_ecb, decrypt_ecb, encrypt_cbc, decrypt_cbc};
+    use crate::hex::parse_hex;
+    use crate::padding::Padding;
+    use crate::Error;
+
+    /// an 8 byte toy cipher, only here to show that the modes don't assume 16 byte blocks
+    struct RotateXor(u8);
+
+    impl BlockCipher for RotateXor {
+        fn block_size(&self) -> usize {
+            8
+        }
+
+        fn encrypt_block(&self, block: &mut [u8]) {
+            block.rotate_left(3);
+            block.iter_mut().for_each(|b| *b ^= self.0);
+        }
+
+        fn decrypt_block(&self, block: &mut [u8]) {
+            block.iter_mut().for_each(|b| *b ^= self.0);
+            block.rotate_right(3);
+        }
+    }
+
+    const SP800_38A_KEY: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+    const SP800_38A_IV: &str = "000102030405060708090a0b0c0d0e0f";
+    const SP800_38A_PLAIN: &str = "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51";
+
+    #[test]
+    fn cbc_sp800_38a() {
+        let cipher = Aes::new(&parse_hex(SP800_38A_KEY).unwrap()).unwrap();
+        let iv = parse_hex(SP800_38A_IV).unwrap();
+        let mut buf = parse_hex(SP800_38A_PLAIN).unwrap();
+
+        cbc_encrypt(&cipher, &iv, &mut buf).unwrap();
+        assert_eq!(parse_hex("7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2").unwrap(), buf);
+
+        cbc_decrypt(&cipher, &iv, &mut buf).unwrap();
+        assert_eq!(parse_hex(SP800_38A_PLAIN).unwrap(), buf);
+    }
+
+    #[test]
+    fn cfb_sp800_38a() {
+        let cipher = Aes::new(&parse_hex(SP800_38A_KEY).unwrap()).unwrap();
+        let iv = parse_hex(SP800_38A_IV).unwrap();
+        let mut buf = parse_hex(SP800_38A_PLAIN).unwrap();
+
+        cfb_encrypt(&cipher, &iv, &mut buf).unwrap();
+        assert_eq!(parse_hex("3b3fd92eb72dad20333449f8e83cfb4ac8a64537a0b3a93fcde3cdad9f1ce58b").unwrap(), buf);
+
+        cfb_decrypt(&cipher, &iv, &mut buf).unwrap();
+        assert_eq!(parse_hex(SP800_38A_PLAIN).unwrap(), buf);
+    }
+
+    #[test]
+    fn ofb_sp800_38a() {
+        let cipher = Aes::new(&parse_hex(SP800_38A_KEY).unwrap()).unwrap();
+        let iv = parse_hex(SP800_38A_IV).unwrap();
+        let mut buf = parse_hex(SP800_38A_PLAIN).unwrap();
+
+        ofb_encrypt(&cipher, &iv, &mut buf).unwrap();
+        assert_eq!(parse_hex("3b3fd92eb72dad20333449f8e83cfb4a7789508d16918f03f53c52dac54ed825").unwrap(), buf);
+
+        ofb_decrypt(&cipher, &iv, &mut buf).unwrap();
+        assert_eq!(parse_hex(SP800_38A_PLAIN).unwrap(), buf);
+    }
+
+    #[test]
+    fn ctr_sp800_38a() {
+        let cipher = Aes::new(&parse_hex(SP800_38A_KEY).unwrap()).unwrap();
+        let mut initial = [0u8; 16];
+        initial.copy_from_slice(&parse_hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff").unwrap());
+        let layout = CounterLayout::BigEndian128(initial);
+        let mut buf = parse_hex(SP800_38A_PLAIN).unwrap();
+
+        ctr_encrypt(&cipher, &layout, &mut buf).unwrap();
+        assert_eq!(parse_hex("874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff").unwrap(), buf);
+
+        ctr_decrypt(&cipher, &layout, &mut buf).unwrap();
+        assert_eq!(parse_hex(SP800_38A_PLAIN).unwrap(), buf);
+    }
+
+    #[test]
+    fn ctr_key_stream_seek() {
+        let cipher = Aes::new(&parse_hex(SP800_38A_KEY).unwrap()).unwrap();
+        let layout = CounterLayout::LittleEndian64(7);
+
+        let mut full = vec![0; 300];
+        ctr_encrypt(&cipher, &layout, &mut full).unwrap();
+
+        let mut key_stream = CtrKeyStream::new(&cipher, layout).unwrap();
+        for offset in &[0, 1, 15, 16, 17, 31, 32, 63, 80, 99, 200, 299, 5] {
+            let mut part = vec![0; 300 - offset];
+            key_stream.seek(*offset as u64);
+            key_stream.apply(&mut part);
+
+            assert_eq!(full[*offset..].to_vec(), part);
+        }
+    }
+
+    #[test]
+    fn ctr_key_stream_in_pieces() {
+        let cipher = Aes::new(&parse_hex(SP800_38A_KEY).unwrap()).unwrap();
+        let layout = CounterLayout::LittleEndian64(7);
+
+        let mut full = vec![0; 500];
+        ctr_encrypt(&cipher, &layout, &mut full).unwrap();
+
+        for piece_size in &[1, 9, 16, 40, 130] {
+            let mut key_stream = CtrKeyStream::new(&cipher, layout).unwrap();
+            let mut pieces = vec![0; 500];
+            for chunk in pieces.chunks_mut(*piece_size) {
+                key_stream.apply(chunk);
+            }
+
+            assert_eq!(full, pieces);
+        }
+    }
+
+    #[test]
+    fn counter_block_layouts() {
+        let layout = CounterLayout::LittleEndian64(0x0102030405060708);
+        assert_eq!([8, 7, 6, 5, 4, 3, 2, 1, 2, 1, 0, 0, 0, 0, 0, 0], layout.counter_block(0x0102));
+
+        let layout = CounterLayout::BigEndian128([0xff; 16]);
+        assert_eq!([0; 16], layout.counter_block(1));
+
+        let mut initial = [0; 16];
+        initial[7] = 1;
+        initial[15] = 0xff;
+        let layout = CounterLayout::BigEndian128(initial);
+        let mut expected = [0; 16];
+        expected[7] = 1;
+        expected[14] = 1;
+        assert_eq!(expected, layout.counter_block(1));
+    }
+
+    #[test]
+    fn ctr_wrong_block_size() {
+        let cipher = RotateXor(0x5a);
+        let mut buf = vec![0; 16];
+
+        let result = ctr_encrypt(&cipher, &CounterLayout::LittleEndian64(0), &mut buf);
+
+        assert!(matches!(result.err().unwrap(), Error::Generic("ctr needs a 16 byte block cipher")));
+    }
+
+    #[test]
+    fn stream_modes_partial_block() {
+        let cipher = Aes::new(&parse_hex(SP800_38A_KEY).unwrap()).unwrap();
+        let iv = parse_hex(SP800_38A_IV).unwrap();
+        let plain = parse_hex(SP800_38A_PLAIN).unwrap();
+
+        let mut buf = plain[..21].to_vec();
+        cfb_encrypt(&cipher, &iv, &mut buf).unwrap();
+        assert_eq!(parse_hex("3b3fd92eb72dad20333449f8e83cfb4ac8a64537a0").unwrap(), buf);
+        cfb_decrypt(&cipher, &iv, &mut buf).unwrap();
+        assert_eq!(plain[..21].to_vec(), buf);
+
+        let mut buf = plain[..21].to_vec();
+        ofb_encrypt(&cipher, &iv, &mut buf).unwrap();
+        assert_eq!(parse_hex("3b3fd92eb72dad20333449f8e83cfb4a7789508d16").unwrap(), buf);
+        ofb_decrypt(&cipher, &iv, &mut buf).unwrap();
+        assert_eq!(plain[..21].to_vec(), buf);
+    }
+
+    #[test]
+    fn cbc_decrypt_across_batches() {
+        let cipher = Aes::new(&parse_hex(SP800_38A_KEY).unwrap()).unwrap();
+        let iv = parse_hex(SP800_38A_IV).unwrap();
+
+        for blocks in 0..20 {
+            let plain: Vec<u8> = (0..blocks * 16).map(|i| i as u8).collect();
+
+            let mut buf = plain.clone();
+            cbc_encrypt(&cipher, &iv, &mut buf).unwrap();
+            cbc_decrypt(&cipher, &iv, &mut buf).unwrap();
+
+            assert_eq!(plain, buf);
+        }
+    }
+
+    #[test]
+    fn modes_with_8_byte_blocks() {
+        let cipher = RotateXor(0x5a);
+        let iv = [1, 2, 3, 4, 5, 6, 7, 8];
+        let plain = b"sixteen byte msg".to_vec();
+
+        let mut buf = plain.clone();
+        ecb_encrypt(&cipher, &mut buf).unwrap();
+        assert_ne!(plain, buf);
+        ecb_decrypt(&cipher, &mut buf).unwrap();
+        assert_eq!(plain, buf);
+
+        let mut buf = plain.clone();
+        cbc_encrypt(&cipher, &iv, &mut buf).unwrap();
+        assert_ne!(buf[..8], buf[8..]);
+        cbc_decrypt(&cipher, &iv, &mut buf).unwrap();
+        assert_eq!(plain, buf);
+
+        let mut buf = plain.clone();
+        cfb_encrypt(&cipher, &iv, &mut buf).unwrap();
+        cfb_decrypt(&cipher, &iv, &mut buf).unwrap();
+        assert_eq!(plain, buf);
+    }
+
+    #[test]
+    fn padded_modes_with_8_byte_blocks() {
+        let cipher = RotateXor(0x5a);
+        let iv = [1, 2, 3, 4, 5, 6, 7, 8];
+        let plain = b"not a whole block".to_vec();
+
+        let cipher_text = encrypt_ecb(&cipher, &plain, Padding::Pkcs7).unwrap();
+        assert_eq!(24, cipher_text.len());
+        assert_eq!(plain, decrypt_ecb(&cipher, &cipher_text, Padding::Pkcs7).unwrap());
+
+        let cipher_text = encrypt_cbc(&cipher, &plain, &iv, Padding::Pkcs7).unwrap();
+        assert_eq!(24, cipher_text.len());
+        assert_eq!(plain, decrypt_cbc(&cipher, &cipher_text, &iv, Padding::Pkcs7).unwrap());
+    }
+
+    #[test]
+    fn wrong_iv_length() {
+        let cipher = RotateXor(0x5a);
+        let mut buf = vec![0; 16];
+
+        let result = cbc_encrypt(&cipher, &[0; 16], &mut buf);
+
+        assert!(matches!(result.err().unwrap(), Error::Generic("iv must be one block long")));
+    }
+
+    #[test]
+    fn ecb_not_block_multiple() {
+        let cipher = RotateXor(0x5a);
+        let mut buf = vec![0; 12];
+
+        let result = ecb_encrypt(&cipher, &mut buf);
+
+        assert!(matches!(result.err().unwrap(), Error::Generic("buffer isn't a multiple of the block size")));
+    }
+}
\ No newline at end of file
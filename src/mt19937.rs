@@ -0,0 +1,173 @@
+use crate::Error;
+
+const N: usize = 624;
+const M: usize = 397;
+const MATRIX_A: u32 = 0x9908_b0df;
+const UPPER_MASK: u32 = 0x8000_0000;
+const LOWER_MASK: u32 = 0x7fff_ffff;
+
+/// The 32 bit Mersenne Twister from Matsumoto and Nishimura.
+#[derive(Clone)]
+pub struct Mt19937 {
+    state: [u32; N],
+    index: usize,
+}
+
+impl Mt19937 {
+    pub fn new(seed: u32) -> Mt19937 {
+        let mut mt = Mt19937 { state: [0; N], index: N };
+        mt.seed(seed);
+        mt
+    }
+
+    /// Starts over from seed, the same as a new generator would.
+    pub fn seed(&mut self, seed: u32) {
+        self.state[0] = seed;
+        for i in 1..N {
+            let prev = self.state[i - 1];
+            self.state[i] = 1_812_433_253u32.wrapping_mul(prev ^ (prev >> 30)).wrapping_add(i as u32);
+        }
+        self.index = N;
+    }
+
+    /// A generator that continues from state with index words of it already
+    /// handed out, at N the next call to extract twists first.
+    pub fn from_state(state: [u32; N], index: usize) -> Result<Mt19937, Error> {
+        if index > N {
+            return Err(Error::Generic("index is past the end of the state"));
+        }
+
+        Ok(Mt19937 { state, index })
+    }
+
+    fn twist(&mut self) {
+        for i in 0..N {
+            let y = (self.state[i] & UPPER_MASK) | (self.state[(i + 1) % N] & LOWER_MASK);
+            let mag = if y & 1 == 0 { 0 } else { MATRIX_A };
+            self.state[i] = self.state[(i + M) % N] ^ (y >> 1) ^ mag;
+        }
+        self.index = 0;
+    }
+
+    pub fn extract(&mut self) -> u32 {
+        if self.index >= N {
+            self.twist();
+        }
+
+        let y = self.state[self.index];
+        self.index += 1;
+
+        temper(y)
+    }
+}
+
+fn temper(mut y: u32) -> u32 {
+    y ^= y >> 11;
+    y ^= (y << 7) & 0x9d2c_5680;
+    y ^= (y << 15) & 0xefc6_0000;
+    y ^ (y >> 18)
+}
+
+/// Undoes a right shift xor, each pass gets shift more bits right.
+fn undo_right(y: u32, shift: u32) -> u32 {
+    let mut x = y;
+    for _ in 0..32 / shift {
+        x = y ^ (x >> shift);
+    }
+    x
+}
+
+/// Undoes a left shift xor with a mask, the same way from the bottom up.
+fn undo_left(y: u32, shift: u32, mask: u32) -> u32 {
+    let mut x = y;
+    for _ in 0..32 / shift {
+        x = y ^ ((x << shift) & mask);
+    }
+    x
+}
+
+/// Gets back the state word an output was tempered from.
+pub fn untemper(y: u32) -> u32 {
+    let y = undo_right(y, 18);
+    let y = undo_left(y, 15, 0xefc6_0000);
+    let y = undo_left(y, 7, 0x9d2c_5680);
+    undo_right(y, 11)
+}
+
+/// Rebuilds a generator from 624 outputs in a row that started right after
+/// a twist, it then predicts whatever the original gives next.
+pub fn clone_mt19937(outputs: &[u32]) -> Result<Mt19937, Error> {
+    if outputs.len() != N {
+        return Err(Error::Generic("need exactly 624 outputs"));
+    }
+
+    let mut state = [0; N];
+    for (s, o) in state.iter_mut().zip(outputs) {
+        *s = untemper(*o);
+    }
+
+    Mt19937::from_state(state, N)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mt19937::{Mt19937, temper, untemper, clone_mt19937};
+
+    #[test]
+    fn reference_outputs() {
+        let expected = [3499211612, 581869302, 3890346734, 3586334585, 545404204,
+            4161255391, 3922919429, 949333985, 2715962298, 1323567403];
+        let mut mt = Mt19937::new(5489);
+
+        for e in expected.iter() {
+            assert_eq!(*e, mt.extract());
+        }
+    }
+
+    #[test]
+    fn ten_thousandth_output() {
+        let mut mt = Mt19937::new(5489);
+
+        let result = (0..10000).map(|_| mt.extract()).last().unwrap();
+
+        assert_eq!(4123659995, result);
+    }
+
+    #[test]
+    fn reseed_starts_over() {
+        let mut mt = Mt19937::new(1);
+        let first = mt.extract();
+        mt.extract();
+
+        mt.seed(1);
+
+        assert_eq!(first, mt.extract());
+    }
+
+    #[test]
+    fn untemper_inverts_temper() {
+        for y in [0, 1, 0xffff_ffff, 0x8000_0000, 0x1234_5678, 0xdead_beef].iter() {
+            assert_eq!(*y, untemper(temper(*y)));
+        }
+    }
+
+    #[test]
+    fn from_state_continues() {
+        let mut mt = Mt19937::new(42);
+        for _ in 0..700 {
+            mt.extract();
+        }
+
+        let mut copy = Mt19937::from_state(mt.state, mt.index).unwrap();
+
+        for _ in 0..1000 {
+            assert_eq!(mt.extract(), copy.extract());
+        }
+        assert!(Mt19937::from_state([0; 624], 625).is_err());
+    }
+
+    #[test]
+    fn clone_needs_624() {
+        assert!(clone_mt19937(&[0; 623]).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use crate::Error;
+use crate::mt19937::Mt19937;
+use crate::random::{random_bytes, random_range};
+use crate::xor::{xor, xor_in_place};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+// The challenge 24 stream cipher, MT19937 seeded with the key gives one key
+// stream byte per output, the low eight bits of it.
+
+fn key_stream(seed: u32, len: usize) -> Vec<u8> {
+    let mut mt = Mt19937::new(seed);
+
+    (0..len).map(|_| mt.extract() as u8).collect()
+}
+
+pub fn encrypt_mt(input: &[u8], key: u16) -> Result<Vec<u8>, Error> {
+    xor(input, &key_stream(key as u32, input.len()))
+}
+
+pub fn decrypt_mt(input: &[u8], key: u16) -> Result<Vec<u8>, Error> {
+    encrypt_mt(input, key)
+}
+
+/// Encrypts known behind 5 to 20 random bytes, like the challenge does.
+pub fn encrypt_with_random_prefix(known: &[u8], key: u16) -> Result<Vec<u8>, Error> {
+    let mut plain_text = random_bytes(random_range(5, 21));
+    plain_text.extend(known);
+
+    encrypt_mt(&plain_text, key)
+}
+
+/// Runs check on every seed in seeds spread over all cores, and returns
+/// the first one any thread finds. The others stop once one is found.
+fn search_seeds<F>(seeds: RangeInclusive<u32>, check: F) -> Option<u32>
+    where F: Fn(u32) -> bool + Sync {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()) as u64;
+    let start = *seeds.start() as u64;
+    let count = (*seeds.end() as u64 + 1).saturating_sub(start);
+    let chunk = ((count + threads - 1) / threads).max(1);
+    let found = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let handles = (0..threads).map(|t| {
+            let (check, found) = (&check, &found);
+            scope.spawn(move || {
+                let low = start + t * chunk;
+                let high = (low + chunk).min(start + count);
+                for seed in low..high {
+                    if found.load(Ordering::Relaxed) {
+                        return None;
+                    }
+                    if check(seed as u32) {
+                        found.store(true, Ordering::Relaxed);
+                        return Some(seed as u32);
+                    }
+                }
+                None
+            })
+        }).collect::<Vec<_>>();
+
+        handles.into_iter().filter_map(|h| h.join().unwrap()).next()
+    })
+}
+
+/// Finds the 16 bit key from a cipher text that ends in known plain text,
+/// by trying every key against the key stream under the known part.
+pub fn recover_mt_key(cipher_text: &[u8], known: &[u8]) -> Result<u16, Error> {
+    if known.is_empty() || known.len() > cipher_text.len() {
+        return Err(Error::Generic("known plain text doesn't fit the cipher text"));
+    }
+
+    let offset = cipher_text.len() - known.len();
+    let mut wanted = cipher_text[offset..].to_vec();
+    xor_in_place(&mut wanted, known);
+
+    search_seeds(0..=u16::MAX as u32, |key| key_stream(key, cipher_text.len())[offset..] == wanted[..])
+        .map(|key| key as u16)
+        .ok_or(Error::Generic("no key gives the known plain text"))
+}
+
+/// A 16 byte password reset token from MT19937 seeded with the current time.
+pub fn password_reset_token<C>(mut clock: C) -> Result<Vec<u8>, Error>
+    where C: FnMut() -> Result<u64, Error> {
+    Ok(key_stream(clock()? as u32, 16))
+}
+
+/// True if the token is what password_reset_token would have given at some
+/// time in the window seconds up to now.
+pub fn is_time_seeded_token(token: &[u8], now: u64, window: u64) -> bool {
+    let seeds = now.saturating_sub(window) as u32..=now as u32;
+
+    search_seeds(seeds, |seed| key_stream(seed, token.len()) == token).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mt_cipher::{encrypt_mt, decrypt_mt, encrypt_with_random_prefix, recover_mt_key, password_reset_token,
+                           is_time_seeded_token, search_seeds};
+    use crate::random::random_bytes;
+
+    #[test]
+    fn roundtrip() {
+        let plain_text = b"a plain text that's longer than a few bytes";
+        let cipher_text = encrypt_mt(plain_text, 1234).unwrap();
+
+        assert_ne!(plain_text.to_vec(), cipher_text);
+        assert_eq!(plain_text.to_vec(), decrypt_mt(&cipher_text, 1234).unwrap());
+        assert_ne!(plain_text.to_vec(), decrypt_mt(&cipher_text, 1235).unwrap());
+    }
+
+    #[test]
+    fn recovers_key() {
+        let known = [b'A'; 14];
+        for key in [0, 1, 31337, u16::MAX].iter() {
+            let cipher_text = encrypt_with_random_prefix(&known, *key).unwrap();
+
+            assert_eq!(*key, recover_mt_key(&cipher_text, &known).unwrap());
+        }
+    }
+
+    #[test]
+    fn known_must_fit() {
+        assert!(recover_mt_key(&[1, 2], &[1, 2, 3]).is_err());
+        assert!(recover_mt_key(&[1, 2], &[]).is_err());
+    }
+
+    #[test]
+    fn search_covers_the_ends() {
+        assert_eq!(Some(10), search_seeds(10..=20, |s| s == 10));
+        assert_eq!(Some(20), search_seeds(10..=20, |s| s == 20));
+        assert_eq!(Some(u32::MAX), search_seeds(u32::MAX - 5..=u32::MAX, |s| s == u32::MAX));
+        assert_eq!(None, search_seeds(10..=20, |s| s == 21));
+    }
+
+    #[test]
+    fn reset_token() {
+        let token = password_reset_token(|| Ok(1_700_000_000)).unwrap();
+
+        assert_eq!(16, token.len());
+        assert!(is_time_seeded_token(&token, 1_700_000_500, 1000));
+        assert!(!is_time_seeded_token(&token, 1_700_002_000, 1000));
+        assert!(!is_time_seeded_token(&random_bytes(16), 1_700_000_500, 1000));
+    }
+}
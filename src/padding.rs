@@ -0,0 +1,361 @@
+use crate::Error;
+use crate::random::random_fill;
+
+/// Why the padding on a buffer was rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaddingError {
+    /// there is no data, so not even a pad byte
+    Empty,
+    /// the data isn't a whole number of blocks
+    WrongLength,
+    /// the byte that marks the padding can't be right
+    InvalidPadByte,
+    /// the pad bytes don't agree with each other or with the pad length
+    InconsistentPadBytes,
+}
+
+/// How a message is filled out to a whole number of blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Padding {
+    /// no padding, the message must already be a whole number of blocks
+    None,
+    /// every pad byte is the number of pad bytes
+    Pkcs7,
+    /// zeros, then the number of pad bytes
+    AnsiX923,
+    /// 0x80, then zeros
+    Iso7816,
+    /// random bytes, then the number of pad bytes
+    Iso10126,
+    /// zeros, only added when the message isn't whole blocks. Trailing zeros
+    /// in the message can't be told apart from the padding.
+    Zero,
+}
+
+impl Padding {
+    /// Pads the first msg_len bytes of buf in place and returns the padded
+    /// length, buf needs to have room for the padding.
+    pub fn pad(&self, buf: &mut [u8], msg_len: usize, block_size: usize) -> Result<usize, Error> {
+        if block_size == 0 {
+            return Err(Error::Generic("block size must be > 0"));
+        }
+        if block_size > 255 && self.stores_length() {
+            return Err(Error::Generic("block size too large for the padding"));
+        }
+
+        let nr_of_bytes_to_extend = match (self, msg_len % block_size) {
+            (Padding::None, 0) | (Padding::Zero, 0) => 0,
+            (Padding::None, _) => return Err(PaddingError::WrongLength.into()),
+            (_, 0) => block_size,
+            (_, i) => block_size - i
+        };
+        let len = msg_len + nr_of_bytes_to_extend;
+        if len > buf.len() {
+            return Err(Error::Generic("buffer has no room for the padding"));
+        }
+
+        let pad = &mut buf[msg_len..len];
+        match self {
+            Padding::None => {},
+            Padding::Pkcs7 => {
+                for b in pad.iter_mut() {
+                    *b = nr_of_bytes_to_extend as u8;
+                }
+            },
+            Padding::AnsiX923 | Padding::Iso10126 => {
+                if *self == Padding::Iso10126 {
+                    random_fill(pad);
+                } else {
+                    for b in pad.iter_mut() {
+                        *b = 0;
+                    }
+                }
+                pad[nr_of_bytes_to_extend - 1] = nr_of_bytes_to_extend as u8;
+            },
+            Padding::Iso7816 => {
+                for b in pad.iter_mut() {
+                    *b = 0;
+                }
+                pad[0] = 0x80;
+            },
+            Padding::Zero => {
+                for b in pad.iter_mut() {
+                    *b = 0;
+                }
+            },
+        }
+
+        Ok(len)
+    }
+
+    /// Checks the padding on buf and returns the length of the message
+    /// without it.
+    pub fn unpadded_len(&self, buf: &[u8], block_size: usize) -> Result<usize, Error> {
+        if block_size == 0 {
+            return Err(Error::Generic("block size must be > 0"));
+        }
+        if buf.is_empty() && self.always_pads() {
+            return Err(PaddingError::Empty.into());
+        }
+        if buf.len() % block_size != 0 {
+            return Err(PaddingError::WrongLength.into());
+        }
+
+        match self {
+            Padding::None => Ok(buf.len()),
+            Padding::Pkcs7 => {
+                let pad_len = length_byte(buf, block_size)?;
+                if buf[buf.len() - pad_len..].iter().any(|b| *b as usize != pad_len) {
+                    return Err(PaddingError::InconsistentPadBytes.into());
+                }
+                Ok(buf.len() - pad_len)
+            },
+            Padding::AnsiX923 => {
+                let pad_len = length_byte(buf, block_size)?;
+                if buf[buf.len() - pad_len..buf.len() - 1].iter().any(|b| *b != 0) {
+                    return Err(PaddingError::InconsistentPadBytes.into());
+                }
+                Ok(buf.len() - pad_len)
+            },
+            Padding::Iso10126 => Ok(buf.len() - length_byte(buf, block_size)?),
+            Padding::Iso7816 => {
+                let last_block = &buf[buf.len() - block_size..];
+                match last_block.iter().rposition(|b| *b != 0) {
+                    Some(i) if last_block[i] == 0x80 => Ok(buf.len() - block_size + i),
+                    _ => Err(PaddingError::InvalidPadByte.into()),
+                }
+            },
+            Padding::Zero => {
+                let zeros = buf.iter().rev().take(block_size - 1).take_while(|b| **b == 0).count();
+                Ok(buf.len() - zeros)
+            },
+        }
+    }
+
+    /// the schemes that write the pad length into the last byte
+    fn stores_length(&self) -> bool {
+        matches!(self, Padding::Pkcs7 | Padding::AnsiX923 | Padding::Iso10126)
+    }
+
+    /// the schemes that add a whole block when the message is already aligned
+    fn always_pads(&self) -> bool {
+        matches!(self, Padding::Pkcs7 | Padding::AnsiX923 | Padding::Iso10126 | Padding::Iso7816)
+    }
+}
+
+/// reads the pad length from the last byte, it has to be 1..=block_size
+fn length_byte(buf: &[u8], block_size: usize) -> Result<usize, Error> {
+    let pad_len = buf[buf.len() - 1] as usize;
+    if pad_len == 0 || pad_len > block_size {
+        return Err(PaddingError::InvalidPadByte.into());
+    }
+    Ok(pad_len)
+}
+
+/// The padding scheme by the name used on the command line.
+pub fn parse_padding(name: &str) -> Result<Padding, Error> {
+    match name {
+        "none" => Ok(Padding::None),
+        "pkcs7" => Ok(Padding::Pkcs7),
+        "x923" => Ok(Padding::AnsiX923),
+        "iso7816" => Ok(Padding::Iso7816),
+        "iso10126" => Ok(Padding::Iso10126),
+        "zero" => Ok(Padding::Zero),
+        _ => Err(Error::Generic("unknown padding, use none, pkcs7, x923, iso7816, iso10126 or zero")),
+    }
+}
+
+pub fn add_padding(plain_text: &mut Vec<u8>, block_size: usize) -> Result<(), Error> {
+    if block_size == 0 {
+        return Err(Error::Generic("block size must be > 0"));
+    }
+
+    let len = plain_text.len();
+    plain_text.resize(len - len % block_size + block_size, 0);
+    Padding::Pkcs7.pad(plain_text, len, block_size)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub fn remove_padding(plain_text: &mut Vec<u8>, block_size: usize) -> Result<(), Error> {
+    let len = Padding::Pkcs7.unpadded_len(plain_text, block_size)?;
+    plain_text.truncate(len);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Error;
+    use crate::padding::{Padding, PaddingError, add_padding, remove_padding, parse_padding};
+
+    const ALL: [Padding; 6] = [Padding::None, Padding::Pkcs7, Padding::AnsiX923, Padding::Iso7816, Padding::Iso10126, Padding::Zero];
+
+    #[test]
+    fn pad_unpad_roundtrip() {
+        for padding in ALL.iter() {
+            for block_size in [8, 16, 255].iter() {
+                for length in 0..(block_size * 2 + 1) {
+                    if *padding == Padding::None && length % block_size != 0 {
+                        continue;
+                    }
+                    let msg = vec![length as u8 | 1; length];
+                    let mut buf = vec![0u8; length + block_size];
+                    buf[..length].copy_from_slice(&msg);
+
+                    let len = padding.pad(&mut buf, length, *block_size).unwrap();
+                    assert_eq!(0, len % block_size);
+                    assert_eq!(length, padding.unpadded_len(&buf[..len], *block_size).unwrap());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn known_padding_bytes() {
+        let mut buf = [1u8; 8];
+
+        assert_eq!(8, Padding::Pkcs7.pad(&mut buf, 5, 8).unwrap());
+        assert_eq!([1, 1, 1, 1, 1, 3, 3, 3], buf);
+        assert_eq!(8, Padding::AnsiX923.pad(&mut buf, 5, 8).unwrap());
+        assert_eq!([1, 1, 1, 1, 1, 0, 0, 3], buf);
+        assert_eq!(8, Padding::Iso7816.pad(&mut buf, 5, 8).unwrap());
+        assert_eq!([1, 1, 1, 1, 1, 0x80, 0, 0], buf);
+        assert_eq!(8, Padding::Iso10126.pad(&mut buf, 5, 8).unwrap());
+        assert_eq!(3, buf[7]);
+        assert_eq!(8, Padding::Zero.pad(&mut buf, 5, 8).unwrap());
+        assert_eq!([1, 1, 1, 1, 1, 0, 0, 0], buf);
+    }
+
+    #[test]
+    fn no_padding_needs_whole_blocks() {
+        let mut buf = [0u8; 16];
+
+        assert_eq!(16, Padding::None.pad(&mut buf, 16, 16).unwrap());
+        let result = Padding::None.pad(&mut buf, 15, 16);
+        assert!(matches!(result.err().unwrap(), Error::PaddingError(PaddingError::WrongLength)));
+    }
+
+    #[test]
+    fn pad_needs_room() {
+        let mut buf = [0u8; 16];
+
+        let result = Padding::Pkcs7.pad(&mut buf, 16, 16);
+        assert!(matches!(result.err().unwrap(), Error::Generic("buffer has no room for the padding")));
+
+        let result = Padding::Pkcs7.pad(&mut buf, 17, 16);
+        assert!(matches!(result.err().unwrap(), Error::Generic("buffer has no room for the padding")));
+    }
+
+    #[test]
+    fn bad_padding_rejected() {
+        let reason = |result: Result<usize, Error>| match result {
+            Err(Error::PaddingError(reason)) => reason,
+            _ => panic!("expected a padding error"),
+        };
+
+        assert_eq!(PaddingError::InconsistentPadBytes, reason(Padding::AnsiX923.unpadded_len(&[1, 1, 1, 1, 1, 1, 0, 3], 8)));
+        assert_eq!(PaddingError::InvalidPadByte, reason(Padding::Iso7816.unpadded_len(&[1, 1, 1, 1, 1, 1, 0, 0], 8)));
+        assert_eq!(PaddingError::InvalidPadByte, reason(Padding::Iso7816.unpadded_len(&[0; 8], 8)));
+        assert_eq!(PaddingError::InvalidPadByte, reason(Padding::Iso10126.unpadded_len(&[1, 1, 1, 1, 1, 1, 1, 9], 8)));
+        assert_eq!(PaddingError::WrongLength, reason(Padding::Pkcs7.unpadded_len(&[4; 8], 16)));
+        assert_eq!(PaddingError::Empty, reason(Padding::Iso7816.unpadded_len(&[], 8)));
+    }
+
+    #[test]
+    fn padding_names() {
+        for (name, padding) in ["none", "pkcs7", "x923", "iso7816", "iso10126", "zero"].iter().zip(ALL.iter()) {
+            assert_eq!(*padding, parse_padding(name).unwrap());
+        }
+        assert!(parse_padding("PKCS7").is_err());
+    }
+
+    #[test]
+    fn remove_padding_test_valid() {
+        for length in 0..17 {
+            let a = vec![length as u8; length];
+
+            let mut a_padded = a.clone();
+            a_padded.append(&mut vec![16 - (length % 16) as u8; 16 - (length % 16)]);
+
+            remove_padding(&mut a_padded, 16).unwrap();
+
+            assert_eq!(a, a_padded);
+        }
+    }
+
+    #[test]
+    fn add_and_remove_padding_test_valid() {
+        for length in 0..17 {
+            let a = vec![length as u8; length];
+
+            let mut a_padded = a.clone();
+            add_padding(&mut a_padded, 16).unwrap();
+
+            remove_padding(&mut a_padded, 16).unwrap();
+
+            assert_eq!(a, a_padded);
+        }
+    }
+
+    #[test]
+    fn remove_padding_test_other_block_size() {
+        let mut a = vec![1, 2, 3, 3, 3];
+
+        remove_padding(&mut a, 5).unwrap();
+
+        assert_eq!(vec![1, 2], a);
+    }
+
+    #[test]
+    fn remove_padding_test_empty_buf() {
+        let mut a = vec![];
+
+        let result = remove_padding(&mut a, 16);
+
+        let result = result.err().unwrap();
+        assert!(matches!(result, Error::PaddingError(PaddingError::Empty)));
+    }
+
+    #[test]
+    fn remove_padding_test_wrong_length() {
+        let mut a = vec![3; 5];
+
+        let result = remove_padding(&mut a, 16);
+
+        let result = result.err().unwrap();
+        assert!(matches!(result, Error::PaddingError(PaddingError::WrongLength)));
+    }
+
+    #[test]
+    fn remove_padding_test_not_padded() {
+        let mut a = vec![20; 16];
+
+        let result = remove_padding(&mut a, 16);
+
+        let result = result.err().unwrap();
+        assert!(matches!(result, Error::PaddingError(PaddingError::InvalidPadByte)));
+    }
+
+    #[test]
+    fn remove_padding_test_not_padded_zero() {
+        let mut a = vec![0; 16];
+
+        let result = remove_padding(&mut a, 16);
+
+        let result = result.err().unwrap();
+        assert!(matches!(result, Error::PaddingError(PaddingError::InvalidPadByte)));
+    }
+
+    #[test]
+    fn remove_padding_test_not_padded_matching_last_byte() {
+        let mut a = vec![3; 16];
+        a[15] = 5;
+
+        let result = remove_padding(&mut a, 16);
+
+        let result = result.err().unwrap();
+        assert!(matches!(result, Error::PaddingError(PaddingError::InconsistentPadBytes)));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::Error;
+use crate::aes::{Aes128, encrypt_cbc, decrypt_cbc};
+use crate::padding::{Padding, add_padding};
+use crate::random::{random_key, random_range};
+use crate::xor::xor;
+
+/// The challenge 17 server, one of the strings is encrypted under a key and
+/// iv the attacker never sees, and the only other thing exposed is whether a
+/// cipher text decrypts to valid padding.
+pub struct PaddingOracle {
+    cipher: Aes128,
+    iv: [u8; 16],
+    cipher_text: Vec<u8>,
+}
+
+impl PaddingOracle {
+    /// picks one of the plain texts at random
+    pub fn new(plain_texts: &[Vec<u8>]) -> Result<PaddingOracle, Error> {
+        if plain_texts.is_empty() {
+            return Err(Error::Generic("no plain texts to pick from"));
+        }
+
+        let cipher = Aes128::new(&random_key());
+        let iv = random_key();
+        let plain_text = &plain_texts[random_range(0, plain_texts.len())];
+        let cipher_text = encrypt_cbc(&cipher, plain_text, &iv, Padding::Pkcs7)?;
+
+        Ok(PaddingOracle { cipher, iv, cipher_text })
+    }
+
+    pub fn iv(&self) -> &[u8; 16] {
+        &self.iv
+    }
+
+    pub fn cipher_text(&self) -> &[u8] {
+        &self.cipher_text
+    }
+
+    /// true when the cipher text decrypts to valid padding, every other
+    /// error is passed on rather than leaking through the answer
+    pub fn padding_valid(&self, iv: &[u8; 16], cipher_text: &[u8]) -> Result<bool, Error> {
+        match decrypt_cbc(&self.cipher, cipher_text, iv, Padding::Pkcs7) {
+            Ok(_) => Ok(true),
+            Err(Error::PaddingError(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The result of a padding oracle attack, with how many times the oracle
+/// had to be asked.
+pub struct Recovered {
+    pub plain_text: Vec<u8>,
+    pub queries: usize,
+}
+
+/// Finds the block cipher decryption of block, before it gets xored with
+/// the previous block, by sending it with a forged iv and walking the
+/// padding up from one byte to a full block.
+fn intermediate_block<F>(block: &[u8; 16], oracle: &mut F, queries: &mut usize) -> Result<[u8; 16], Error>
+    where F: FnMut(&[u8; 16], &[u8]) -> Result<bool, Error> {
+    let mut intermediate = [0u8; 16];
+
+    for pos in (0..16).rev() {
+        let pad = (16 - pos) as u8;
+        let mut iv = [0u8; 16];
+        for (iv, intermediate) in iv.iter_mut().zip(intermediate.iter()).skip(pos + 1) {
+            *iv = intermediate ^ pad;
+        }
+
+        let mut found = false;
+        for guess in 0..=255u8 {
+            iv[pos] = guess;
+            *queries += 1;
+            if !oracle(&iv, block)? {
+                continue;
+            }
+
+            // the last byte can also be valid because it made a longer
+            // padding, like 02 02, changing the byte before it rules that out
+            if pos == 15 {
+                iv[14] ^= 1;
+                *queries += 1;
+                let still_valid = oracle(&iv, block)?;
+                iv[14] ^= 1;
+                if !still_valid {
+                    continue;
+                }
+            }
+
+            intermediate[pos] = guess ^ pad;
+            found = true;
+            break;
+        }
+
+        if !found {
+            return Err(Error::Generic("oracle accepted none of the guesses"));
+        }
+    }
+
+    Ok(intermediate)
+}
+
+/// Decrypts a CBC cipher text using nothing but a yes/no answer on whether
+/// the padding is valid, the padding is stripped from the result.
+pub fn padding_oracle_decrypt<F>(iv: &[u8; 16], cipher_text: &[u8], mut oracle: F) -> Result<Recovered, Error>
+    where F: FnMut(&[u8; 16], &[u8]) -> Result<bool, Error> {
+    if cipher_text.is_empty() || cipher_text.len() % 16 != 0 {
+        return Err(Error::Generic("block size isn't 16"));
+    }
+
+    let mut queries = 0;
+    let mut plain_text = Vec::with_capacity(cipher_text.len());
+    let mut previous = &iv[..];
+
+    for block in cipher_text.chunks_exact(16) {
+        let mut target = [0u8; 16];
+        target.copy_from_slice(block);
+
+        let intermediate = intermediate_block(&target, &mut oracle, &mut queries)?;
+        plain_text.extend(xor(&intermediate, previous)?);
+        previous = block;
+    }
+
+    let len = Padding::Pkcs7.unpadded_len(&plain_text, 16)?;
+    plain_text.truncate(len);
+
+    Ok(Recovered { plain_text, queries })
+}
+
+/// A cipher text made without the key, with how many times the oracle had
+/// to be asked.
+pub struct Forged {
+    pub iv: [u8; 16],
+    pub cipher_text: Vec<u8>,
+    pub queries: usize,
+}
+
+/// CBC-R, encrypts plain_text under the oracle's key by working backwards
+/// from a random last block. Each block's intermediate state comes from the
+/// oracle, and the block before it is picked to turn that into the wanted
+/// plain text, ending with the iv.
+pub fn padding_oracle_encrypt<F>(plain_text: &[u8], mut oracle: F) -> Result<Forged, Error>
+    where F: FnMut(&[u8; 16], &[u8]) -> Result<bool, Error> {
+    let mut padded = plain_text.to_vec();
+    add_padding(&mut padded, 16)?;
+
+    let mut queries = 0;
+    let mut blocks = vec![random_key()];
+
+    for plain_block in padded.chunks_exact(16).rev() {
+        let intermediate = intermediate_block(&blocks[blocks.len() - 1], &mut oracle, &mut queries)?;
+
+        let mut previous = [0u8; 16];
+        previous.copy_from_slice(&xor(&intermediate, plain_block)?);
+        blocks.push(previous);
+    }
+
+    let iv = blocks.pop().unwrap();
+    let cipher_text = blocks.iter().rev().flatten().copied().collect();
+
+    Ok(Forged { iv, cipher_text, queries })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Error;
+    use crate::aes::decrypt_aes_cbc;
+    use crate::random::random_key;
+    use crate::padding_oracle::{PaddingOracle, padding_oracle_decrypt, padding_oracle_encrypt};
+
+    #[test]
+    fn recovers_every_length() {
+        for len in 0..40 {
+            let plain_text: Vec<u8> = (0..len as u8).map(|b| b.wrapping_mul(37)).collect();
+            let oracle = PaddingOracle::new(std::slice::from_ref(&plain_text)).unwrap();
+
+            let result = padding_oracle_decrypt(oracle.iv(), oracle.cipher_text(), |iv, cipher_text| oracle.padding_valid(iv, cipher_text)).unwrap();
+
+            assert_eq!(plain_text, result.plain_text);
+            assert!(result.queries >= oracle.cipher_text().len());
+            assert!(result.queries <= oracle.cipher_text().len() * 257);
+        }
+    }
+
+    #[test]
+    fn rejects_partial_blocks() {
+        let result = padding_oracle_decrypt(&[0; 16], &[0; 20], |_, _| Ok(true));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn forged_cipher_text_decrypts_under_hidden_key() {
+        let key = random_key();
+        let oracle = |iv: &[u8; 16], cipher_text: &[u8]| match decrypt_aes_cbc(cipher_text, &key, iv) {
+            Ok(_) => Ok(true),
+            Err(Error::PaddingError(_)) => Ok(false),
+            Err(e) => Err(e),
+        };
+
+        for message in [&b""[..], b"admin=true", b"exactly sixteen!", b"comment1=cooking%20MCs;userdata=x;admin=true;comment2=%20like%20a%20pound%20of%20bacon"].iter() {
+            let forged = padding_oracle_encrypt(message, oracle).unwrap();
+
+            assert_eq!(0, forged.cipher_text.len() % 16);
+            assert!(forged.cipher_text.len() > message.len());
+            assert!(forged.queries > 0);
+            assert_eq!(message.to_vec(), decrypt_aes_cbc(&forged.cipher_text, &key, &forged.iv).unwrap());
+        }
+    }
+
+    #[test]
+    fn forge_through_challenge_oracle() {
+        let oracle = PaddingOracle::new(&[b"unrelated".to_vec()]).unwrap();
+        let message = b"this was never encrypted by the server";
+
+        let forged = padding_oracle_encrypt(message, |iv, cipher_text| oracle.padding_valid(iv, cipher_text)).unwrap();
+        let result = padding_oracle_decrypt(&forged.iv, &forged.cipher_text, |iv, cipher_text| oracle.padding_valid(iv, cipher_text)).unwrap();
+
+        assert_eq!(message.to_vec(), result.plain_text);
+    }
+}
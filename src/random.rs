@@ -0,0 +1,79 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+// There is no rng in std, so this hashes nothing with the SipHash keys of a
+// new RandomState. std seeds those keys from the os once per thread and
+// then just increments them for every RandomState after that, so the output
+// only looks random. This is NOT a CSPRNG, it is good for throwaway keys in
+// the challenges and nothing else.
+
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// fills buf with random bytes without allocating
+pub fn random_fill(buf: &mut [u8]) {
+    for chunk in buf.chunks_mut(8) {
+        chunk.copy_from_slice(&random_u64().to_le_bytes()[..chunk.len()]);
+    }
+}
+
+pub fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    random_fill(&mut bytes);
+    bytes
+}
+
+pub fn random_key() -> [u8; 16] {
+    let mut key = [0u8; 16];
+    key.copy_from_slice(&random_bytes(16));
+    key
+}
+
+pub fn random_u32() -> u32 {
+    random_u64() as u32
+}
+
+/// uniform in low..high, which must not be empty
+pub fn random_range(low: usize, high: usize) -> usize {
+    assert!(low < high, "random_range needs low < high, got {}..{}", low, high);
+
+    low + (random_u64() % (high - low) as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::random::{random_bytes, random_key, random_range};
+
+    #[test]
+    fn random_bytes_length() {
+        for len in 0..20 {
+            assert_eq!(len, random_bytes(len).len());
+        }
+    }
+
+    #[test]
+    fn random_keys_differ() {
+        assert_ne!(random_key(), random_key());
+    }
+
+    #[test]
+    fn random_range_bounds() {
+        for _ in 0..1000 {
+            let r = random_range(5, 11);
+            assert!((5..11).contains(&r));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "random_range needs low < high, got 3..3")]
+    fn random_range_empty() {
+        random_range(3, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "random_range needs low < high, got 4..2")]
+    fn random_range_backwards() {
+        random_range(4, 2);
+    }
+}
\ No newline at end of file
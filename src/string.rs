@@ -56,6 +56,33 @@ pub fn calc_percentage_diff(percentages: HashMap<char, f64>) -> f64 {
     accumulated_diff
 }
 
+/// How far bytes are from english text, lower is closer. Letters are
+/// compared to the english frequencies ignoring case, punctuation costs a
+/// little and anything that isn't printable costs a lot.
+pub fn score_english(input: &[u8]) -> f64 {
+    let letters = input.iter()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_lowercase() as char)
+        .collect::<Vec<char>>();
+
+    let mut score = if letters.is_empty() {
+        100.0
+    } else {
+        calc_percentage_diff(calc_char_percentages(&letters))
+    };
+
+    for c in input {
+        score += match c {
+            b'a'..=b'z' | b'A'..=b'Z' | b' ' => 0.0,
+            b',' | b'.' | b'\'' | b'-' | b';' | b':' | b'!' | b'?' | b'"' => 5.0,
+            0x21..=0x7e => 50.0,
+            _ => 200.0,
+        };
+    }
+
+    score
+}
+
 pub fn hamming_distance(a: &[u8], b: &[u8]) -> Result<usize, Error> {
     if a.len() != b.len() {
         return Err(Error::Generic("a and b is not of the same length"))
@@ -75,7 +102,7 @@ pub fn trim_and_join(a: &str) -> String {
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
-    use crate::string::{calc_char_percentages, hamming_distance};
+    use crate::string::{calc_char_percentages, hamming_distance, score_english};
 
     #[test]
     fn calc_char_percentages_test() {
@@ -83,7 +110,7 @@ mod tests {
         expected.insert('a', 50.0);
         expected.insert('b', 50.0);
         assert_eq!(expected,
-                   calc_char_percentages(&vec!['a', 'a', 'b', 'b']));
+                   calc_char_percentages(&['a', 'a', 'b', 'b']));
     }
 
     #[test]
@@ -94,7 +121,7 @@ mod tests {
         expected.insert('b', 25.0);
         expected.insert('B', 25.0);
         assert_eq!(expected,
-                   calc_char_percentages(&vec!['a', 'A', 'b', 'B']));
+                   calc_char_percentages(&['a', 'A', 'b', 'B']));
     }
 
     #[test]
@@ -106,4 +133,10 @@ mod tests {
     fn hamming_distance_test_2() {
         assert_eq!(6, hamming_distance("jake".as_bytes(), "fire".as_bytes()).unwrap())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn score_english_prefers_text() {
+        assert!(score_english(b"the quick brown fox") < score_english(b"qzx jvk qzxj vkq zx"));
+        assert!(score_english(b"plain old text") < score_english(&[0x01, 0x9f, 0x7e, 0x00]));
+    }
+}
@@ -0,0 +1,88 @@
+use crate::Error;
+use crate::mt19937::Mt19937;
+use crate::random::random_range;
+use std::ops::RangeInclusive;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the epoch, the clock the scenario uses outside of tests.
+pub fn unix_time() -> Result<u64, Error> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .map_err(|_| Error::Generic("clock is before the epoch"))
+}
+
+/// The challenge 22 setup, after it has run.
+pub struct TimestampScenario {
+    /// the first output of the generator
+    pub output: u32,
+    /// the simulated time when the output was handed over
+    pub finished_at: u64,
+    /// kept to check a crack against
+    pub seed: u32,
+}
+
+/// Waits a simulated wait seconds, seeds MT19937 with the time, waits again
+/// and hands out the first output. Time only passes in the numbers, so the
+/// clock can be anything and nothing sleeps.
+pub fn timestamp_scenario<C>(mut clock: C, wait: RangeInclusive<u64>) -> Result<TimestampScenario, Error>
+    where C: FnMut() -> Result<u64, Error> {
+    let random_wait = || random_range(*wait.start() as usize, *wait.end() as usize + 1) as u64;
+
+    let seeded_at = clock()? + random_wait();
+    let seed = seeded_at as u32;
+    let output = Mt19937::new(seed).extract();
+    let finished_at = seeded_at + random_wait();
+
+    Ok(TimestampScenario { output, finished_at, seed })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CrackedSeed {
+    pub seed: u32,
+    pub candidates: usize,
+}
+
+/// Tries every time in range as the seed, latest first since the seeding
+/// is most likely recent, until one gives output as its first value.
+pub fn crack_timestamp_seed(output: u32, range: RangeInclusive<u64>) -> Result<CrackedSeed, Error> {
+    for (i, time) in range.rev().enumerate() {
+        let seed = time as u32;
+        if Mt19937::new(seed).extract() == output {
+            return Ok(CrackedSeed { seed, candidates: i + 1 });
+        }
+    }
+
+    Err(Error::Generic("no seed in the range gives the output"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timestamp_seed::{timestamp_scenario, crack_timestamp_seed, unix_time};
+
+    #[test]
+    fn cracks_seed_with_fixed_clock() {
+        let scenario = timestamp_scenario(|| Ok(1_500_000_000), 40..=1000).unwrap();
+
+        let result = crack_timestamp_seed(scenario.output, scenario.finished_at - 2000..=scenario.finished_at).unwrap();
+
+        assert_eq!(scenario.seed, result.seed);
+        assert_eq!((scenario.finished_at - result.seed as u64 + 1) as usize, result.candidates);
+        assert!((41..=1001).contains(&result.candidates));
+    }
+
+    #[test]
+    fn seed_outside_range() {
+        let scenario = timestamp_scenario(|| Ok(1_500_000_000), 40..=40).unwrap();
+
+        assert!(crack_timestamp_seed(scenario.output, scenario.finished_at - 30..=scenario.finished_at).is_err());
+        assert_eq!(41, crack_timestamp_seed(scenario.output, scenario.finished_at - 40..=scenario.finished_at).unwrap().candidates);
+    }
+
+    #[test]
+    fn real_clock_works() {
+        let scenario = timestamp_scenario(unix_time, 0..=0).unwrap();
+
+        assert_eq!(scenario.finished_at as u32, scenario.seed);
+    }
+}
@@ -10,13 +10,19 @@ pub fn xor(a: &[u8], b: &[u8]) -> Result<Vec<u8>, Error> {
     Ok(a.iter().zip(b.iter()).map(|(a, b)| a ^ b).collect())
 }
 
+/// xors b into a, b must be at least as long as a
+pub fn xor_in_place(a: &mut [u8], b: &[u8]) {
+    for (a, b) in a.iter_mut().zip(b.iter()) {
+        *a ^= b;
+    }
+}
+
 pub fn xor_char(a: &[char], b: u32) -> Result<Vec<char>, Error> {
     Ok(a.iter().map(|a| std::char::from_u32((*a as u32) ^ b).unwrap()).collect())
 }
 
 pub fn xor_repeat(a: &[u8], b: &[u8]) -> Vec<u8> {
-    let mut result = Vec::new();
-    result.resize(a.len(), 0);
+    let mut result = vec![0; a.len()];
     for i in 0..a.len() {
         result[i] = a[i] ^ b[i % b.len()];
     }
@@ -71,7 +77,7 @@ pub fn guess_xor_byte_with_space(input: &[u8]) -> u8 {
 
 pub fn guess_xor_byte_with_space_vec(input: &[Vec<u8>]) -> Vec<u8> {
     input.iter().map(|v| {
-        guess_xor_byte_with_space(&v)
+        guess_xor_byte_with_space(v)
     }).collect()
 }
 
@@ -96,16 +102,16 @@ pub fn find_xored_english_string(input: &[String]) -> String {
 
 #[cfg(test)]
 mod tests {
-    use crate::xor::{xor, xor_char, xor_repeat, guess_xor_byte_with_space_vec};
+    use crate::xor::{xor, xor_char, xor_repeat, xor_in_place, guess_xor_byte_with_space_vec};
     use std::char::from_u32;
 
     #[test]
     fn guess_xor_byte_vec_test() {
         let s = "this is my line, and i am sticking to it no matter what";
 
-        let enc_a = xor_repeat(s.as_bytes(), &vec![b'a']);
-        let enc_b = xor_repeat(s.as_bytes(), &vec![b'b']);
-        let enc_c = xor_repeat(s.as_bytes(), &vec![b'c']);
+        let enc_a = xor_repeat(s.as_bytes(), b"a");
+        let enc_b = xor_repeat(s.as_bytes(), b"b");
+        let enc_c = xor_repeat(s.as_bytes(), b"c");
 
         let result = guess_xor_byte_with_space_vec(&[enc_a, enc_b, enc_c]);
 
@@ -114,17 +120,24 @@ mod tests {
 
     #[test]
     fn xor_test() {
-        assert_eq!(vec![92, 25, 41], xor(&vec![127, 93, 111], &vec![35, 68, 70]).unwrap());
+        assert_eq!(vec![92, 25, 41], xor(&[127, 93, 111], &[35, 68, 70]).unwrap());
+    }
+
+    #[test]
+    fn xor_in_place_test() {
+        let mut a = [127, 93, 111];
+        xor_in_place(&mut a, &[35, 68, 70]);
+        assert_eq!([92, 25, 41], a);
     }
 
     #[test]
     fn xor_repeat_test() {
-        assert_eq!(vec![92, 25, 41, 126, 43], xor_repeat(&vec![127, 93, 111, 93, 111],
-                                                &vec![35, 68, 70]));
+        assert_eq!(vec![92, 25, 41, 126, 43], xor_repeat(&[127, 93, 111, 93, 111],
+                                                &[35, 68, 70]));
     }
 
     #[test]
     fn xor_char_test() {
-        assert_eq!(vec![from_u32(57).unwrap(), from_u32(27).unwrap(), from_u32(41).unwrap()], xor_char(&vec![from_u32(127).unwrap(), from_u32(93).unwrap(), from_u32(111).unwrap()], 70).unwrap());
+        assert_eq!(vec![from_u32(57).unwrap(), from_u32(27).unwrap(), from_u32(41).unwrap()], xor_char(&[from_u32(127).unwrap(), from_u32(93).unwrap(), from_u32(111).unwrap()], 70).unwrap());
     }
 }
\ No newline at end of file